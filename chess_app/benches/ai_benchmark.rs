@@ -0,0 +1,46 @@
+use chess_app::ai::{ChessAI, Difficulty};
+use chess_app::{Color, GameState};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+const MIDDLE_GAME: &str = "r1bq1rk1/pp2bppp/2n1pn2/2pp4/3P4/2PBPN2/PP1N1PPP/R1BQ1RK1 w - - 0 8";
+
+/// Set up a game from a benchmark FEN string
+fn game_from_fen(fen: &str) -> GameState {
+    GameState::from_fen(fen).expect("benchmark FEN should be valid")
+}
+
+fn bench_move_generation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generate_legal_moves");
+    for (name, fen) in [("start", None), ("kiwipete", Some(KIWIPETE)), ("middle_game", Some(MIDDLE_GAME))] {
+        let game = fen.map_or_else(GameState::new, game_from_fen);
+        group.bench_function(name, |b| b.iter(|| black_box(&game).generate_legal_moves()));
+    }
+    group.finish();
+}
+
+fn bench_perft(c: &mut Criterion) {
+    let mut group = c.benchmark_group("perft_4");
+    group.sample_size(10);
+    for (name, fen) in [("start", None), ("kiwipete", Some(KIWIPETE))] {
+        let game = fen.map_or_else(GameState::new, game_from_fen);
+        group.bench_function(name, |b| b.iter(|| game.clone().perft(black_box(4))));
+    }
+    group.finish();
+}
+
+fn bench_search(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_best_move");
+    group.sample_size(10);
+    
+    // Advanced searches to depth 4
+    let ai = ChessAI::new(Color::White, Difficulty::Advanced);
+    for (name, fen) in [("depth_4_middle_game", MIDDLE_GAME), ("depth_4_kiwipete", KIWIPETE)] {
+        let game = game_from_fen(fen);
+        group.bench_function(name, |b| b.iter(|| ai.get_best_move(black_box(&game))));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_move_generation, bench_perft, bench_search);
+criterion_main!(benches);
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use crate::state::{GameState, Move};
+use crate::types::{Piece, Position};
+
+/// The book built into the binary, so the AI knows some openings without any files.
+const EMBEDDED_BOOK: &str = include_str!("book.txt");
+
+/// Why an opening book couldn't be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BookError {
+    /// The book file couldn't be read
+    Io(String),
+    /// A move on the given line, counting from 1, isn't written as e.g. "e2e4" or "e7e8q"
+    InvalidMove { line: usize, text: String },
+    /// A move on the given line, counting from 1, isn't legal after the moves before it
+    IllegalMove { line: usize, text: String },
+}
+
+/// Replies to known positions, keyed by `GameState::position_key`.
+///
+/// A book is written as lines of moves in coordinate notation, each played from the
+/// starting position. Blank lines and lines starting with '#' are skipped.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OpeningBook {
+    replies: HashMap<String, Vec<Move>>,
+}
+
+impl OpeningBook {
+    /// Parse a book from its text
+    pub fn parse(text: &str) -> Result<Self, BookError> {
+        let mut book = OpeningBook::default();
+        
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            
+            let mut game = GameState::new();
+            for token in line.split_whitespace() {
+                let game_move = parse_coordinate_move(token).ok_or_else(|| BookError::InvalidMove {
+                    line: line_number,
+                    text: token.to_string(),
+                })?;
+                
+                let key = game.position_key();
+                game.make_move(&game_move).map_err(|_| BookError::IllegalMove {
+                    line: line_number,
+                    text: token.to_string(),
+                })?;
+                
+                let replies = book.replies.entry(key).or_default();
+                if !replies.contains(&game_move) {
+                    replies.push(game_move);
+                }
+            }
+        }
+        
+        Ok(book)
+    }
+    
+    /// Load a book from a file in the same format as the embedded one
+    pub fn load(path: &Path) -> Result<Self, BookError> {
+        let text = fs::read_to_string(path).map_err(|err| BookError::Io(err.to_string()))?;
+        Self::parse(&text)
+    }
+    
+    /// The book built into the binary, parsed the first time it's needed
+    pub fn embedded() -> Arc<OpeningBook> {
+        static BOOK: OnceLock<Arc<OpeningBook>> = OnceLock::new();
+        let book = BOOK.get_or_init(|| {
+            Arc::new(OpeningBook::parse(EMBEDDED_BOOK).expect("embedded opening book should parse"))
+        });
+        Arc::clone(book)
+    }
+    
+    /// The book replies to the position in `game_state`, most preferred first
+    pub fn replies(&self, game_state: &GameState) -> &[Move] {
+        self.replies.get(&game_state.position_key()).map_or(&[], Vec::as_slice)
+    }
+    
+    /// Number of positions with a book reply
+    pub fn len(&self) -> usize {
+        self.replies.len()
+    }
+    
+    /// Whether the book knows no positions
+    pub fn is_empty(&self) -> bool {
+        self.replies.is_empty()
+    }
+}
+
+/// Read a move like "e2e4", with a trailing piece letter for promotions as in "e7e8q"
+fn parse_coordinate_move(text: &str) -> Option<Move> {
+    if !text.is_ascii() || !(4..=5).contains(&text.len()) {
+        return None;
+    }
+    
+    let promotion_piece = match text.chars().nth(4) {
+        Some(letter) => Some(Piece::from_fen_char(letter)?.piece_type),
+        None => None,
+    };
+    
+    Some(Move {
+        from: Position::from_notation(&text[..2])?,
+        to: Position::from_notation(&text[2..4])?,
+        promotion_piece,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_embedded_book_answers_start_position() {
+        let book = OpeningBook::embedded();
+        assert!(!book.is_empty());
+        
+        let e4 = parse_coordinate_move("e2e4").unwrap();
+        let replies = book.replies(&GameState::new());
+        assert_eq!(replies.first(), Some(&e4));
+        assert!(replies.contains(&parse_coordinate_move("d2d4").unwrap()));
+        
+        let mut game = GameState::new();
+        game.make_move(&e4).unwrap();
+        assert!(book.replies(&game).contains(&parse_coordinate_move("e7e5").unwrap()));
+    }
+    
+    #[test]
+    fn test_parse_reports_bad_lines() {
+        assert_eq!(
+            OpeningBook::parse("# comment\n\ne2e4 e7e5\ne2e4 e7e9"),
+            Err(BookError::InvalidMove { line: 4, text: String::from("e7e9") })
+        );
+        assert_eq!(
+            OpeningBook::parse("e2e4 e2e4"),
+            Err(BookError::IllegalMove { line: 1, text: String::from("e2e4") })
+        );
+    }
+}
@@ -1,135 +1,1130 @@
 use crate::board::Board;
-use crate::types::{Color, Position, PieceType};
-use crate::state::GameState;
-use crate::gui::Difficulty;
+use crate::types::{Color, Piece, Position, PieceType};
+use crate::state::{GameResult, GameState, Move};
+use crate::zobrist;
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::{Arc, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
+pub mod book;
+mod tables;
+
+pub use book::OpeningBook;
+
+#[derive(Debug, Clone)]
+pub enum Difficulty {
+    Beginner,
+    Intermediate,
+    Advanced,
+}
+
+impl Difficulty {
+    /// How many plies the AI searches at this difficulty
+    pub fn search_depth(&self) -> u8 {
+        match self {
+            Difficulty::Beginner => 2,
+            Difficulty::Intermediate => 3,
+            Difficulty::Advanced => 4,
+        }
+    }
+
+    /// How long the AI may think per move at this difficulty
+    pub fn time_budget(&self) -> Duration {
+        match self {
+            Difficulty::Beginner => Duration::from_millis(500),
+            Difficulty::Intermediate => Duration::from_millis(1500),
+            Difficulty::Advanced => Duration::from_millis(3000),
+        }
+    }
+}
+
+/// Weights of the terms in the AI's evaluation, in centipawns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvalParams {
+    /// Count material only, skipping every positional term, for faster deep searches
+    pub material_only: bool,
+    /// Penalty for each pawn beyond the first on a file
+    pub doubled_pawn_penalty: i32,
+    /// Penalty for each pawn with no friendly pawns on the neighbouring files
+    pub isolated_pawn_penalty: i32,
+    /// Bonus for having the bishop pair when the opponent doesn't
+    pub bishop_pair_bonus: i32,
+    /// Penalty per unit of `Board::attack_weight_near_king` against one's own king
+    pub king_attack_penalty: i32,
+    /// Bonus for each central square occupied, and each one attacked, with all pieces on;
+    /// it shrinks as pieces come off
+    pub center_control_bonus: i32,
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        EvalParams {
+            material_only: false,
+            doubled_pawn_penalty: 25,
+            isolated_pawn_penalty: 20,
+            bishop_pair_bonus: 50,
+            king_attack_penalty: 5,
+            center_control_bonus: 10,
+        }
+    }
+}
+
+/// One candidate move from an analysis, from the point of view of the side to move.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnalysisLine {
+    pub san: String,
+    /// In centipawns
+    pub evaluation: i32,
+}
+
+/// How good a move was, judged by the evaluation it gives away compared to the best move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveQuality {
+    /// As good as the engine's choice
+    Best,
+    /// Loses less than half a pawn
+    Good,
+    /// Loses up to a pawn
+    Inaccuracy,
+    /// Loses up to three pawns
+    Mistake,
+    /// Loses more than three pawns, or isn't a legal move
+    Blunder,
+}
+
+/// Material deficit in centipawns at which the AI gives up
+const RESIGN_THRESHOLD: i32 = 1500;
+
+/// Material in knights, bishops, rooks and queens of both sides at the start, in centipawns
+const STARTING_PIECE_MATERIAL: i32 = 6200;
+
+/// Score of a checkmate, far beyond any material count. The side delivering it scores a
+/// little more for each ply of search left, so sooner mates are preferred.
+const MATE_SCORE: i32 = 100_000;
+
+/// Bound of the search window, beyond every mate score
+const INFINITE_SCORE: i32 = 1_000_000;
+
+/// How a score in the transposition table relates to the position's true value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    /// The search failed high, so the value is at least the score
+    Lower,
+    /// The search failed low, so the value is at most the score
+    Upper,
+}
+
+/// A searched position's score, from the side to move's point of view.
+#[derive(Debug, Clone, Copy)]
+struct TtEntry {
+    depth: u8,
+    score: i32,
+    bound: Bound,
+}
+
+#[derive(Debug, Clone)]
 pub struct ChessAI {
     color: Color,
     depth: u8,
+    time_budget: Duration,
+    max_nodes: Option<u64>,
+    eval_params: EvalParams,
+    pondering: bool,
+    book: Option<Arc<OpeningBook>>,
+    // Picks among the book's replies, the same ones for the same seed and position; `None`
+    // always plays the most preferred
+    book_seed: Option<u64>,
+    // Scores of positions already searched, keyed by Zobrist hash with the side to move;
+    // `None` when disabled. Cleared at the start of each search.
+    table: Option<RefCell<HashMap<u64, TtEntry>>>,
+}
+
+/// A search running in the background on the position expected after the opponent's reply.
+///
+/// Both the prediction of the reply and the search after it run on the background thread,
+/// and nothing here waits for them.
+pub struct PonderHandle {
+    predicted: Arc<OnceLock<(Position, Position)>>,
+    search: JoinHandle<Option<(Position, Position)>>,
+}
+
+impl PonderHandle {
+    /// The opponent move the background search assumed, once it has been predicted.
+    pub fn predicted_move(&self) -> Option<(Position, Position)> {
+        self.predicted.get().copied()
+    }
+    
+    /// Whether the background search has finished, so that `reply_to` can use its move.
+    pub fn is_finished(&self) -> bool {
+        self.search.is_finished()
+    }
+    
+    /// The background search's move if it has finished and the opponent played the predicted
+    /// move. Otherwise the search is abandoned and `None` is returned.
+    pub fn reply_to(self, played: (Position, Position)) -> Option<(Position, Position)> {
+        if !self.search.is_finished() || self.predicted_move() != Some(played) {
+            return None;
+        }
+        
+        self.search.join().ok().flatten()
+    }
 }
 
 impl ChessAI {
     pub fn new(color: Color, difficulty: Difficulty) -> Self {
-        let depth = match difficulty {
-            Difficulty::Beginner => 2,
-            Difficulty::Intermediate => 3,
-            Difficulty::Advanced => 4,
-        };
+        ChessAI {
+            color,
+            depth: difficulty.search_depth(),
+            time_budget: difficulty.time_budget(),
+            max_nodes: None,
+            eval_params: EvalParams::default(),
+            pondering: false,
+            book: Some(OpeningBook::embedded()),
+            book_seed: Some(RandomState::new().hash_one(())),
+            table: Some(RefCell::default()),
+        }
+    }
+    
+    /// The color the AI plays.
+    pub fn color(&self) -> Color {
+        self.color
+    }
+    
+    /// Enable or disable searching ahead while the opponent is thinking.
+    pub fn set_pondering(&mut self, enabled: bool) {
+        self.pondering = enabled;
+    }
+    
+    /// Replace the evaluation weights.
+    pub fn set_eval_params(&mut self, params: EvalParams) {
+        self.eval_params = params;
+    }
+    
+    /// Evaluate material only, or go back to including the positional terms.
+    pub fn set_material_only(&mut self, enabled: bool) {
+        self.eval_params.material_only = enabled;
+    }
+    
+    /// Stop searching once `nodes` positions have been visited, in addition to the time budget.
+    pub fn set_max_nodes(&mut self, nodes: u64) {
+        self.max_nodes = Some(nodes);
+    }
+    
+    /// Reuse the scores of positions reached again by another move order, which is the
+    /// default, or search every position afresh.
+    pub fn set_transposition_table(&mut self, enabled: bool) {
+        self.table = enabled.then(RefCell::default);
+    }
+    
+    fn clear_table(&self) {
+        if let Some(table) = &self.table {
+            table.borrow_mut().clear();
+        }
+    }
+    
+    fn out_of_nodes(&self, nodes: u64) -> bool {
+        self.max_nodes.is_some_and(|max| nodes >= max)
+    }
+    
+    /// Whether the AI would agree to a draw in this position, which it does unless it is ahead
+    pub fn should_accept_draw(&self, game_state: &GameState) -> bool {
+        self.evaluate_position(&game_state.board, self.color) <= 0
+    }
+    
+    /// Whether the AI would resign, which it does when hopelessly behind in material
+    pub fn should_resign(&self, game_state: &GameState) -> bool {
+        self.evaluate_position(&game_state.board, self.color) <= -RESIGN_THRESHOLD
+    }
+    
+    /// Start searching the position after the opponent's most likely reply in a background thread.
+    ///
+    /// `game_state` must have the opponent to move. Returns `None` when pondering is disabled,
+    /// or when the opponent has no moves.
+    pub fn ponder(&self, game_state: &GameState) -> Option<PonderHandle> {
+        if !self.pondering || game_state.current_player == self.color || game_state.legal_move_count() == 0 {
+            return None;
+        }
         
-        ChessAI { color, depth }
+        let opponent = ChessAI { color: self.color.opposite(), ..self.clone() };
+        let ai = self.clone();
+        let mut expected = game_state.clone();
+        expected.clear_observer();
+        
+        let predicted = Arc::new(OnceLock::new());
+        let prediction = Arc::clone(&predicted);
+        let search = thread::spawn(move || {
+            // Predict the opponent's reply with a search from their side
+            let (from, to) = opponent.get_best_move(&expected)?;
+            expected.make_move(&Move { from, to, promotion_piece: None }).ok()?;
+            prediction.set((from, to)).ok();
+            
+            ai.get_best_move(&expected)
+        });
+        
+        Some(PonderHandle { predicted, search })
     }
     
+    /// Play the opening book's reply if it has one, otherwise search one ply deeper at a time
+    /// until the maximum depth, the time budget or the node limit is reached, returning the
+    /// best move of the deepest completed search
     pub fn get_best_move(&self, game_state: &GameState) -> Option<(Position, Position)> {
-        let mut alpha = f32::NEG_INFINITY;
-        let beta = f32::INFINITY;
+        if let Some(book_move) = self.book_move(game_state) {
+            return Some(book_move);
+        }
+        self.iterative_deepening(game_state, &mut 0)
+    }
+    
+    /// Replace the opening book, which is the embedded one by default; `None` always searches
+    pub fn set_opening_book(&mut self, book: Option<Arc<OpeningBook>>) {
+        self.book = book;
+    }
+    
+    /// Vary the book replies with `seed`, each new AI having its own random one, or always
+    /// play the most preferred reply with `None`
+    pub fn set_book_seed(&mut self, seed: Option<u64>) {
+        self.book_seed = seed;
+    }
+    
+    /// A book reply that is legal in the position, if any, the more preferred ones more often
+    fn book_move(&self, game_state: &GameState) -> Option<(Position, Position)> {
+        let replies: Vec<_> = self.book.as_ref()?
+            .replies(game_state)
+            .iter()
+            .filter(|reply| game_state.is_legal(reply.from, reply.to))
+            .collect();
+        
+        // Of n replies the first is n times as likely as the last
+        let count = replies.len() as u64;
+        let mut pick = match self.book_seed {
+            Some(seed) => zobrist::mix(seed ^ game_state.hash()) % (count * (count + 1) / 2).max(1),
+            None => 0,
+        };
+        for (index, reply) in replies.iter().enumerate() {
+            let weight = count - index as u64;
+            if pick < weight {
+                return Some((reply.from, reply.to));
+            }
+            pick -= weight;
+        }
+        None
+    }
+    
+    /// Score every move for the AI's color at the full search depth, returning the best
+    /// `count` moves with their evaluations, best first
+    pub fn top_moves(&self, game_state: &GameState, count: usize) -> Vec<((Position, Position), i32)> {
+        self.clear_table();
+        let mut nodes = 0;
+        let mut scored: Vec<_> = self.root_moves(game_state)
+            .into_iter()
+            .map(|(from, to)| {
+                let new_board = Self::board_after(&game_state.board, from, to);
+                
+                // A full window for every move, so each score is exact
+                let depth = self.depth.max(1) - 1;
+                let value = -self.minimax(&new_board, depth, -INFINITE_SCORE, INFINITE_SCORE, self.color.opposite(), &mut nodes);
+                ((from, to), value)
+            })
+            .collect();
+        
+        scored.sort_by_key(|&(_, value)| Reverse(value));
+        scored.truncate(count);
+        scored
+    }
+    
+    /// Analyze the position for the side to move, returning up to `count` lines in SAN
+    pub fn analyze(&self, game_state: &GameState, count: usize) -> Vec<AnalysisLine> {
+        let analyst = ChessAI { color: game_state.current_player, ..self.clone() };
+        
+        analyst.top_moves(game_state, count)
+            .into_iter()
+            .filter_map(|((from, to), evaluation)| {
+                let san = game_state.to_san(&Move { from, to, promotion_piece: None }).ok()?;
+                Some(AnalysisLine { san, evaluation })
+            })
+            .collect()
+    }
+    
+    /// Judge `played`, a move for the side to move in `before`, by how many centipawns it
+    /// loses against the engine's best move at the full search depth
+    pub fn classify_move(&self, before: &GameState, played: &Move) -> MoveQuality {
+        let analyst = ChessAI { color: before.current_player, ..self.clone() };
+        let scored = analyst.top_moves(before, usize::MAX);
+        
+        let best = match scored.first() {
+            Some(&(_, value)) => value,
+            None => return MoveQuality::Blunder,
+        };
+        let value = match scored.iter().find(|((from, to), _)| *from == played.from && *to == played.to) {
+            Some(&(_, value)) => value,
+            None => return MoveQuality::Blunder,
+        };
+        
+        let centipawn_loss = best - value;
+        if centipawn_loss <= 0 {
+            MoveQuality::Best
+        } else if centipawn_loss < 50 {
+            MoveQuality::Good
+        } else if centipawn_loss <= 100 {
+            MoveQuality::Inaccuracy
+        } else if centipawn_loss <= 300 {
+            MoveQuality::Mistake
+        } else {
+            MoveQuality::Blunder
+        }
+    }
+    
+    fn iterative_deepening(&self, game_state: &GameState, nodes: &mut u64) -> Option<(Position, Position)> {
+        self.clear_table();
+        let start = Instant::now();
         let mut best_move = None;
-        let mut best_value = f32::NEG_INFINITY;
+        
+        for depth in 1..=self.depth {
+            if depth > 1 && start.elapsed() >= self.time_budget {
+                break;
+            }
+            
+            // A search cut short by the node limit is only used if nothing better exists
+            let result = self.search_root(game_state, depth, best_move, nodes);
+            if best_move.is_none() || !self.out_of_nodes(*nodes) {
+                best_move = result;
+            }
+            if self.out_of_nodes(*nodes) {
+                break;
+            }
+        }
+        
+        best_move
+    }
+    
+    /// Search every root move to `depth`, starting with `first_move`, usually the best move of
+    /// a shallower search, so good moves raise alpha early and cut off more of the rest
+    fn search_root(&self, game_state: &GameState, depth: u8, first_move: Option<(Position, Position)>, nodes: &mut u64) -> Option<(Position, Position)> {
+        let mut alpha = -INFINITE_SCORE;
+        let beta = INFINITE_SCORE;
+        let mut best_move = None;
+        let mut best_value = -INFINITE_SCORE;
         
         // Get all possible moves
-        let moves = self.generate_moves(&game_state.board, self.color);
+        let mut moves = self.root_moves(game_state);
+        if let Some(index) = first_move.and_then(|first| moves.iter().position(|&m| m == first)) {
+            moves[..=index].rotate_right(1);
+        }
         
         for (from, to) in moves {
+            // Always search at least one move so there is something to play
+            if best_move.is_some() && self.out_of_nodes(*nodes) {
+                break;
+            }
+            
             // Create a new board with the move applied
-            let mut new_board = game_state.board.clone();
-            if let Some(piece) = new_board.get_piece(&from) {
-                new_board.set_piece(to, piece.clone());
-                new_board.remove_piece(&from);
-                
-                // Calculate value using minimax
-                let value = -self.minimax(&new_board, self.depth - 1, -beta, -alpha, self.color.opposite());
-                
-                if value > best_value {
-                    best_value = value;
-                    best_move = Some((from, to));
-                }
-                
-                alpha = alpha.max(value);
+            let new_board = Self::board_after(&game_state.board, from, to);
+            
+            // Calculate value using minimax
+            let value = -self.minimax(&new_board, depth - 1, -beta, -alpha, self.color.opposite(), nodes);
+            
+            if value > best_value {
+                best_value = value;
+                best_move = Some((from, to));
             }
+            
+            alpha = alpha.max(value);
         }
         
         best_move
     }
     
-    fn minimax(&self, board: &Board, depth: u8, mut alpha: f32, beta: f32, color: Color) -> f32 {
+    fn minimax(&self, board: &Board, depth: u8, mut alpha: i32, beta: i32, color: Color, nodes: &mut u64) -> i32 {
+        if self.out_of_nodes(*nodes) {
+            return self.evaluate_position(board, color);
+        }
+        *nodes += 1;
+        
         if depth == 0 {
             return self.evaluate_position(board, color);
         }
         
-        let moves = self.generate_moves(board, color);
+        // A score from a search at least as deep settles this one if it's exact, or if it
+        // is a bound already outside the window
+        let key = board.piece_hash() ^ zobrist::side_to_move_key(color);
+        let stored = self.table.as_ref().and_then(|table| table.borrow().get(&key).copied());
+        if let Some(entry) = stored.filter(|entry| entry.depth >= depth) {
+            match entry.bound {
+                Bound::Exact => return entry.score,
+                Bound::Lower if entry.score >= beta => return entry.score,
+                Bound::Upper if entry.score <= alpha => return entry.score,
+                _ => {}
+            }
+        }
+        let original_alpha = alpha;
+        
+        let mut moves = self.generate_moves(board, color);
+        Self::order_moves(board, &mut moves);
         
+        // Checkmated, and worse the fewer plies it took, or stalemated, which is a draw
+        // however much material is left
         if moves.is_empty() {
-            return self.evaluate_position(board, color);
+            return if board.is_king_in_check(color) { -MATE_SCORE - depth as i32 } else { 0 };
         }
         
-        let mut max_value = f32::NEG_INFINITY;
+        let mut max_value = -INFINITE_SCORE;
         
         for (from, to) in moves {
-            let mut new_board = board.clone();
-            if let Some(piece) = new_board.get_piece(&from) {
-                new_board.set_piece(to, piece.clone());
-                new_board.remove_piece(&from);
-                
-                let value = -self.minimax(&new_board, depth - 1, -beta, -alpha, color.opposite());
-                max_value = max_value.max(value);
-                alpha = alpha.max(value);
-                
-                if alpha >= beta {
-                    break;
-                }
+            let new_board = Self::board_after(board, from, to);
+            
+            let value = -self.minimax(&new_board, depth - 1, -beta, -alpha, color.opposite(), nodes);
+            max_value = max_value.max(value);
+            alpha = alpha.max(value);
+            
+            if alpha >= beta {
+                break;
             }
         }
         
+        // A search cut short by the node limit may have missed better moves
+        if let (Some(table), false) = (&self.table, self.out_of_nodes(*nodes)) {
+            let bound = if max_value <= original_alpha {
+                Bound::Upper
+            } else if max_value >= beta {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+            table.borrow_mut().insert(key, TtEntry { depth, score: max_value, bound });
+        }
+        
         max_value
     }
     
-    fn evaluate_position(&self, board: &Board, color: Color) -> f32 {
-        let mut value = 0.0;
+    fn evaluate_position(&self, board: &Board, color: Color) -> i32 {
+        let mut value = 0;
+        // Knights, bishops, rooks and queens of both sides, for telling how far the game is
+        // from the endgame
+        let mut piece_material = 0;
+        // Piece-square bonuses of every piece but the kings
+        let mut square_bonus = 0;
         
         // Simple material counting
-        for rank in 0..8 {
-            for file in 0..8 {
-                let pos = Position::new(file, rank);
-                if let Some(piece) = board.get_piece(&pos) {
-                    let piece_value = match piece.piece_type {
-                        PieceType::Pawn => 1.0,
-                        PieceType::Knight => 3.0,
-                        PieceType::Bishop => 3.0,
-                        PieceType::Rook => 5.0,
-                        PieceType::Queen => 9.0,
-                        PieceType::King => 0.0, // King's value isn't counted
-                    };
-                    
-                    if piece.color == color {
-                        value += piece_value;
-                    } else {
-                        value -= piece_value;
-                    }
+        for (pos, piece) in board.iter_pieces() {
+            let piece_value = piece_value(piece.piece_type);
+            
+            if piece.color == color {
+                value += piece_value;
+                square_bonus += tables::square_bonus(piece, &pos);
+            } else {
+                value -= piece_value;
+                square_bonus -= tables::square_bonus(piece, &pos);
+            }
+            if piece.piece_type != PieceType::Pawn {
+                piece_material += piece_value;
+            }
+        }
+        
+        if self.eval_params.material_only {
+            return value;
+        }
+        
+        value += self.pawn_structure(board, color) - self.pawn_structure(board, color.opposite());
+        value += self.king_safety(board, color) - self.king_safety(board, color.opposite());
+        
+        // From 1.0 with every piece on the board down to 0.0 once only kings and pawns remain
+        let phase = (piece_material as f32 / STARTING_PIECE_MATERIAL as f32).min(1.0);
+        let center = self.center_control(board, color) - self.center_control(board, color.opposite());
+        value += (phase * center as f32).round() as i32;
+        
+        let king_bonus = |side: Color| {
+            board.find_king(side).map_or(0, |pos| tables::king_bonus(side, &pos, phase))
+        };
+        value += square_bonus + king_bonus(color) - king_bonus(color.opposite());
+        
+        match (board.has_bishop_pair(color), board.has_bishop_pair(color.opposite())) {
+            (true, false) => value + self.eval_params.bishop_pair_bonus,
+            (false, true) => value - self.eval_params.bishop_pair_bonus,
+            _ => value,
+        }
+    }
+    
+    /// Score the pawn structure of one side, which is never positive
+    fn pawn_structure(&self, board: &Board, color: Color) -> i32 {
+        let pawn = Piece::new(PieceType::Pawn, color);
+        let mut pawns_per_file = [0u8; 8];
+        for file in 0..8 {
+            for rank in 0..8 {
+                if board.get_piece(&Position::new_unchecked(file, rank)) == Some(&pawn) {
+                    pawns_per_file[file as usize] += 1;
                 }
             }
         }
         
-        value
+        let mut score = 0;
+        for (file, &count) in pawns_per_file.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            
+            score -= self.eval_params.doubled_pawn_penalty * (count - 1) as i32;
+            
+            let left = file.checked_sub(1).map_or(0, |f| pawns_per_file[f]);
+            let right = pawns_per_file.get(file + 1).copied().unwrap_or(0);
+            if left == 0 && right == 0 {
+                score -= self.eval_params.isolated_pawn_penalty * count as i32;
+            }
+        }
+        
+        score
+    }
+    
+    /// Score the safety of one side's king from the enemy pieces bearing on it, which is never positive
+    fn king_safety(&self, board: &Board, color: Color) -> i32 {
+        -self.eval_params.king_attack_penalty * board.attack_weight_near_king(color)
     }
     
+    /// Score one side's hold on d4, e4, d5 and e5 before scaling for the game phase
+    fn center_control(&self, board: &Board, color: Color) -> i32 {
+        let mut controlled = 0;
+        for (file, rank) in [(3, 3), (4, 3), (3, 4), (4, 4)] {
+            let pos = Position::new_unchecked(file, rank);
+            if board.get_piece(&pos).is_some_and(|piece| piece.color == color) {
+                controlled += 1;
+            }
+            if board.is_square_attacked(&pos, color) {
+                controlled += 1;
+            }
+        }
+        
+        self.eval_params.center_control_bonus * controlled
+    }
+    
+    /// Generate the legal moves of `color`'s pieces, leaving out castling and en passant,
+    /// which depend on the game's history
     fn generate_moves(&self, board: &Board, color: Color) -> Vec<(Position, Position)> {
         let mut moves = Vec::new();
         
-        // Basic move generation (to be expanded)
+        for (from, _) in board.iter_pieces().filter(|(_, piece)| piece.color == color) {
+            for to in board.get_valid_moves(&from) {
+                moves.push((from, to));
+            }
+        }
+        
+        moves
+    }
+    
+    /// Generate the moves to search from the game's position, which unlike deeper plies
+    /// knows the castling rights
+    fn root_moves(&self, game_state: &GameState) -> Vec<(Position, Position)> {
+        let mut moves = self.generate_moves(&game_state.board, self.color);
+        if game_state.current_player == self.color {
+            moves.extend(game_state.castling_moves().iter().map(|m| (m.from, m.to)));
+        }
+        Self::order_moves(&game_state.board, &mut moves);
+        moves
+    }
+    
+    /// Put captures first, the most valuable victims before the rest and, among equal
+    /// victims, the least valuable attackers first; other moves keep their order after them
+    fn order_moves(board: &Board, moves: &mut [(Position, Position)]) {
+        moves.sort_by_key(|(from, to)| {
+            let capture = board.get_piece(to).zip(board.get_piece(from)).map(|(victim, attacker)| {
+                piece_value(victim.piece_type) * 10 - piece_value(attacker.piece_type)
+            });
+            Reverse(capture)
+        });
+    }
+    
+    /// Copy the board with a move applied, moving the rook too when castling and queening
+    /// a pawn that reaches its last rank, as `GameState::make_move` does by default
+    fn board_after(board: &Board, from: Position, to: Position) -> Board {
+        let mut new_board = board.clone();
+        if new_board.is_castling_move(&from, &to) {
+            new_board.castle(&from, &to);
+        } else if let Some(mut piece) = new_board.remove_piece(&from) {
+            if board.requires_promotion(&from, &to) {
+                piece.piece_type = PieceType::Queen;
+            }
+            new_board.set_piece(to, piece);
+        }
+        new_board
+    }
+}
+
+/// Material value of a piece in centipawns; kings aren't counted
+fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Pawn => 100,
+        PieceType::Knight => 300,
+        PieceType::Bishop => 300,
+        PieceType::Rook => 500,
+        PieceType::Queen => 900,
+        PieceType::King => 0,
+    }
+}
+
+/// Play a game between two AIs from `game_state` until it ends or `max_plies` moves are made
+///
+/// Each side may resign or claim an available draw before its move; draws that end the game
+/// automatically are honored too. Returns `None` if the game was still going at the move cap.
+pub fn self_play(white: &ChessAI, black: &ChessAI, mut game_state: GameState, max_plies: usize) -> Option<GameResult> {
+    for _ in 0..max_plies {
+        if let Some(result) = game_state.get_game_result() {
+            return Some(result);
+        }
+        
+        let mover = if game_state.current_player == Color::White { white } else { black };
+        if mover.should_resign(&game_state) {
+            return Some(GameResult::Resignation { winner: mover.color().opposite() });
+        }
+        if let Some(&reason) = game_state.claimable_draws().first() {
+            if mover.should_accept_draw(&game_state) {
+                return Some(GameResult::Draw(reason));
+            }
+        }
+        
+        let (from, to) = mover.get_best_move(&game_state)?;
+        game_state.make_move(&Move { from, to, promotion_piece: None }).ok()?;
+    }
+    
+    game_state.get_game_result()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    
+    /// Flip the board vertically and swap the colors of all pieces
+    fn mirror_vertical(board: &Board) -> Board {
+        let mut mirrored = Board::new();
         for rank in 0..8 {
             for file in 0..8 {
-                let from = Position::new(file, rank);
-                if let Some(piece) = board.get_piece(&from) {
-                    if piece.color == color {
-                        // Get valid moves for this piece
-                        let valid_moves = board.get_valid_moves(&from);
-                        for to in valid_moves {
-                            moves.push((from, to));
-                        }
-                    }
+                if let Some(piece) = board.get_piece(&Position::new_unchecked(file, rank)) {
+                    let flipped = Piece::new(piece.piece_type, piece.color.opposite());
+                    mirrored.set_piece(Position::new_unchecked(file, 7 - rank), flipped);
                 }
             }
         }
+        mirrored
+    }
+    
+    fn assert_symmetric(ai: &ChessAI, board: &Board) {
+        let value = ai.evaluate_position(board, Color::White);
+        let mirrored = ai.evaluate_position(&mirror_vertical(board), Color::White);
+        assert_eq!(value, -mirrored, "asymmetric evaluation for {}", board.to_fen());
+    }
+    
+    #[test]
+    fn test_difficulty_mapping_is_monotonic() {
+        let levels = [Difficulty::Beginner, Difficulty::Intermediate, Difficulty::Advanced];
+        
+        for pair in levels.windows(2) {
+            assert!(pair[1].search_depth() >= pair[0].search_depth());
+            assert!(pair[1].time_budget() >= pair[0].time_budget());
+        }
+    }
+    
+    #[test]
+    fn test_generate_moves_are_legal() {
+        let ai = ChessAI::new(Color::White, Difficulty::Beginner);
+        assert_eq!(ai.generate_moves(&Board::new_game(), Color::White).len(), 20);
+        assert_eq!(ai.generate_moves(&Board::new_game(), Color::Black).len(), 20);
         
-        moves
+        // The knight pinned to its king has no moves, and the king can't step into the
+        // rook's file
+        let board = Board::from_fen("4r2k/8/8/8/8/8/4N3/4K3").unwrap();
+        let moves = ai.generate_moves(&board, Color::White);
+        assert!(moves.iter().all(|(from, _)| *from == Position::new_unchecked(4, 0)));
+        assert!(moves.iter().all(|(_, to)| to.file != 4));
+        assert_eq!(moves.len(), 4);
+    }
+    
+    #[test]
+    fn test_order_moves_puts_best_captures_first() {
+        let ai = ChessAI::new(Color::White, Difficulty::Beginner);
+        let square = |name| Position::from_notation(name).unwrap();
+        
+        // The pawn and the queen can both take the rook, and the pawn can take a pawn too
+        let board = Board::from_fen("4k3/8/8/2r1p3/3P4/8/8/2Q1K3").unwrap();
+        let mut moves = ai.generate_moves(&board, Color::White);
+        ChessAI::order_moves(&board, &mut moves);
+        assert_eq!(moves[..3], [
+            (square("d4"), square("c5")),
+            (square("c1"), square("c5")),
+            (square("d4"), square("e5")),
+        ]);
+        assert!(moves[3..].iter().all(|(_, to)| board.get_piece(to).is_none()));
+    }
+    
+    #[test]
+    fn test_ponder_matches_fresh_search() {
+        let mut ai = ChessAI::new(Color::Black, Difficulty::Beginner);
+        let game = GameState::new();
+        
+        // Disabled by default
+        assert!(ai.ponder(&game).is_none());
+        
+        ai.set_pondering(true);
+        let handle = ai.ponder(&game).unwrap();
+        while !handle.is_finished() {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let predicted = handle.predicted_move().unwrap();
+        
+        let mut played = game.clone();
+        played.make_move(&Move { from: predicted.0, to: predicted.1, promotion_piece: None }).unwrap();
+        
+        let pondered = handle.reply_to(predicted);
+        assert!(pondered.is_some());
+        assert_eq!(pondered, ai.get_best_move(&played));
+    }
+    
+    #[test]
+    fn test_ponder_discarded_on_other_move() {
+        let mut ai = ChessAI::new(Color::Black, Difficulty::Beginner);
+        ai.set_pondering(true);
+        
+        let handle = ai.ponder(&GameState::new()).unwrap();
+        while !handle.is_finished() {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let other = if handle.predicted_move().unwrap().0 == Position::new_unchecked(0, 1) {
+            (Position::new_unchecked(7, 1), Position::new_unchecked(7, 2))
+        } else {
+            (Position::new_unchecked(0, 1), Position::new_unchecked(0, 2))
+        };
+        assert_eq!(handle.reply_to(other), None);
+    }
+    
+    #[test]
+    fn test_analysis_lists_legal_san_moves() {
+        let ai = ChessAI::new(Color::White, Difficulty::Beginner);
+        let mut game = GameState::new();
+        game.apply_san("e4").unwrap();
+        game.apply_san("d5").unwrap();
+        
+        let assert_legal_lines = |game: &GameState| {
+            let lines = ai.analyze(game, 3);
+            assert_eq!(lines.len(), 3);
+            assert!(lines.windows(2).all(|pair| pair[0].evaluation >= pair[1].evaluation));
+            for line in &lines {
+                assert!(game.parse_san(&line.san).is_ok(), "{} isn't legal", line.san);
+            }
+        };
+        
+        // Lines are for whoever is on move, not just the AI's color
+        assert_legal_lines(&game);
+        game.apply_san("exd5").unwrap();
+        assert_legal_lines(&game);
+    }
+    
+    #[test]
+    fn test_classify_move() {
+        let ai = ChessAI::new(Color::Black, Difficulty::Beginner);
+        let mut game = GameState::new();
+        game.apply_san("e4").unwrap();
+        game.apply_san("d5").unwrap();
+        
+        // Qg4 walks into the c8 bishop
+        let hanging = Move { from: Position::new_unchecked(3, 0), to: Position::new_unchecked(6, 3), promotion_piece: None };
+        assert_eq!(ai.classify_move(&game, &hanging), MoveQuality::Blunder);
+        
+        let analyst = ChessAI::new(Color::White, Difficulty::Beginner);
+        let ((from, to), _) = analyst.top_moves(&game, 1)[0];
+        assert_eq!(ai.classify_move(&game, &Move { from, to, promotion_piece: None }), MoveQuality::Best);
+    }
+    
+    #[test]
+    fn test_material_only_ignores_structure() {
+        let mut ai = ChessAI::new(Color::White, Difficulty::Beginner);
+        
+        // Same material, but one side has doubled and isolated pawns
+        let healthy = Board::from_fen("4k3/pppp4/8/8/8/8/PPPP4/4K3").unwrap();
+        let broken = Board::from_fen("4k3/pppp4/8/8/8/2P5/P1P4P/4K3").unwrap();
+        
+        assert!(ai.evaluate_position(&broken, Color::White) < ai.evaluate_position(&healthy, Color::White));
+        
+        ai.set_material_only(true);
+        assert_eq!(ai.evaluate_position(&healthy, Color::White), 0);
+        assert_eq!(ai.evaluate_position(&broken, Color::White), ai.evaluate_position(&healthy, Color::White));
+    }
+    
+    #[test]
+    fn test_bishop_pair_bonus() {
+        let mut ai = ChessAI::new(Color::White, Difficulty::Beginner);
+        
+        // Equal material and pawns, but only White keeps both bishops
+        let board = Board::from_fen("2b1k1n1/pppppppp/8/8/8/8/PPPPPPPP/2B1KB2").unwrap();
+        assert!(board.has_bishop_pair(Color::White));
+        assert!(!board.has_bishop_pair(Color::Black));
+        
+        let white_eval = ai.evaluate_position(&board, Color::White);
+        assert!(white_eval > 0 && white_eval < 100);
+        assert_eq!(ai.evaluate_position(&board, Color::Black), -white_eval);
+        
+        ai.set_material_only(true);
+        assert_eq!(ai.evaluate_position(&board, Color::White), 0);
+    }
+    
+    #[test]
+    fn test_center_control() {
+        let ai = ChessAI::new(Color::White, Difficulty::Beginner);
+        
+        // White's pawns stand on d4 and e4 and cover d5 and e5; Black's are still at home
+        let white_center = Board::from_fen("rnbqkbnr/pppppppp/8/8/3PP3/8/PPP2PPP/RNBQKBNR").unwrap();
+        let black_center = mirror_vertical(&white_center);
+        
+        let term = |board: &Board| ai.center_control(board, Color::White) - ai.center_control(board, Color::Black);
+        assert!(term(&white_center) > 0);
+        assert!(term(&white_center) > term(&black_center));
+        assert!(ai.evaluate_position(&white_center, Color::White) > ai.evaluate_position(&black_center, Color::White));
+        
+        // With only kings and pawns left the term no longer counts
+        let pawn_ending = Board::from_fen("4k3/pppppppp/8/8/3PP3/8/PPP2PPP/4K3").unwrap();
+        let mut no_center = ai.clone();
+        no_center.set_eval_params(EvalParams { center_control_bonus: 0, ..EvalParams::default() });
+        assert_eq!(
+            ai.evaluate_position(&pawn_ending, Color::White),
+            no_center.evaluate_position(&pawn_ending, Color::White)
+        );
+    }
+    
+    #[test]
+    fn test_piece_square_tables_favor_development() {
+        let mut ai = ChessAI::new(Color::White, Difficulty::Beginner);
+        let after = |from: &str, to: &str| {
+            let from = Position::from_notation(from).unwrap();
+            let to = Position::from_notation(to).unwrap();
+            ai.evaluate_position(&ChessAI::board_after(&Board::new_game(), from, to), Color::White)
+        };
+        assert!(after("e2", "e4") > after("a2", "a4"));
+        assert!(after("g1", "f3") > after("g1", "h3"));
+        
+        // Out of book the AI still opens with a central pawn or a knight
+        ai.set_opening_book(None);
+        let game = GameState::new();
+        let (from, _) = ai.get_best_move(&game).unwrap();
+        let piece = game.board.get_piece(&from).unwrap().piece_type;
+        assert!(piece == PieceType::Knight || (piece == PieceType::Pawn && (2..=5).contains(&from.file)),
+            "opened with the {:?} on {}", piece, from.to_notation());
+    }
+    
+    #[test]
+    fn test_plays_book_move_on_first_move() {
+        let game = GameState::new();
+        let mut ai = ChessAI::new(Color::White, Difficulty::Advanced);
+        
+        // Even a search too small to find anything still plays the book move
+        ai.set_max_nodes(0);
+        let (from, to) = ai.get_best_move(&game).unwrap();
+        assert!(OpeningBook::embedded().replies(&game).iter().any(|m| m.from == from && m.to == to));
+        
+        // Without a book the move comes from the search instead
+        ai.set_opening_book(None);
+        ai.set_max_nodes(50);
+        let mut nodes = 0;
+        assert_eq!(ai.get_best_move(&game), ai.iterative_deepening(&game, &mut nodes));
+    }
+    
+    #[test]
+    fn test_book_openings_vary_with_seed() {
+        let book = OpeningBook::embedded();
+        let opening = |seed| {
+            let mut white = ChessAI::new(Color::White, Difficulty::Beginner);
+            let mut black = ChessAI::new(Color::Black, Difficulty::Beginner);
+            white.set_book_seed(seed);
+            black.set_book_seed(seed);
+            
+            let mut game = GameState::new();
+            let mut moves = Vec::new();
+            for _ in 0..5 {
+                let mover = if game.current_player == Color::White { &white } else { &black };
+                let (from, to) = mover.get_best_move(&game).unwrap();
+                assert!(book.replies(&game).iter().any(|m| m.from == from && m.to == to));
+                game.make_move(&Move { from, to, promotion_piece: None }).unwrap();
+                moves.push((from, to));
+            }
+            moves
+        };
+        
+        // Without a seed every game follows the first line of the book
+        let first_line = opening(None);
+        assert_eq!(first_line[0], (Position::new_unchecked(4, 1), Position::new_unchecked(4, 3)));
+        assert_eq!(opening(None), first_line);
+        
+        // A seed always picks the same moves, but other seeds open other ways
+        assert_eq!(opening(Some(7)), opening(Some(7)));
+        let openings: HashSet<_> = (0..8).map(|seed| opening(Some(seed))).collect();
+        assert!(openings.len() > 1, "every seed played {:?}", first_line);
+    }
+    
+    #[test]
+    fn test_transposition_table_saves_nodes() {
+        // King and pawn moves reach the same positions in many orders. A single pawn each
+        // keeps a search deep enough for the table to matter quick in debug builds
+        let game = GameState::from_fen("4k3/8/3p4/8/8/3P4/8/4K3 w - - 0 1").unwrap();
+        let mut ai = ChessAI::new(Color::White, Difficulty::Advanced);
+        
+        let mut with_table = 0;
+        let best = ai.search_root(&game, 6, None, &mut with_table);
+        
+        ai.set_transposition_table(false);
+        let mut without_table = 0;
+        let fresh = ai.search_root(&game, 6, None, &mut without_table);
+        
+        assert_eq!(best, fresh);
+        assert!(with_table * 4 < without_table * 3, "{} nodes with the table, {} without", with_table, without_table);
+    }
+    
+    #[test]
+    fn test_iterative_deepening_matches_fixed_depth() {
+        let ai = ChessAI::new(Color::White, Difficulty::Intermediate);
+        let square = |name| Position::from_notation(name).unwrap();
+        
+        // A hanging queen, and a pawn that can take a rook or a knight
+        for (fen, expected) in [
+            ("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", ("d2", "d5")),
+            ("4k3/8/8/2r1n3/3P4/8/8/4K3 w - - 0 1", ("d4", "c5")),
+        ] {
+            let game = GameState::from_fen(fen).unwrap();
+            let fixed = ai.search_root(&game, ai.depth, None, &mut 0);
+            assert_eq!(fixed, Some((square(expected.0), square(expected.1))));
+            assert_eq!(ai.iterative_deepening(&game, &mut 0), fixed, "in {}", fen);
+        }
+    }
+    
+    #[test]
+    fn test_plays_quickest_mate() {
+        let ai = ChessAI::new(Color::White, Difficulty::Advanced);
+        let square = |name| Position::from_notation(name).unwrap();
+        let play = |game: &mut GameState, (from, to): (Position, Position)| {
+            game.make_move(&Move { from, to, promotion_piece: None }).unwrap();
+        };
+        
+        // Rb8 mates at once, though nearly every other move mates a move later
+        let game = GameState::from_fen("7k/R7/8/8/8/8/8/1R4K1 w - - 0 1").unwrap();
+        assert_eq!(ai.get_best_move(&game), Some((square("b1"), square("b8"))));
+        
+        // With no mate in one the rooks mate in two, whatever Black replies
+        let mut game = GameState::from_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1").unwrap();
+        let first = ai.get_best_move(&game).unwrap();
+        play(&mut game, first);
+        for reply in game.generate_legal_moves() {
+            let mut replied = game.clone();
+            replied.make_move(&reply).unwrap();
+            let mate = ai.get_best_move(&replied).unwrap();
+            play(&mut replied, mate);
+            assert_eq!(replied.get_game_result(), Some(GameResult::Checkmate { winner: Color::White }), "in {}", replied.to_fen());
+        }
+    }
+    
+    #[test]
+    fn test_mate_and_stalemate() {
+        let ai = ChessAI::new(Color::White, Difficulty::Beginner);
+        let square = |name| Position::from_notation(name).unwrap();
+        
+        // The rook mates on the back rank behind Black's own pawns
+        let game = GameState::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        assert_eq!(ai.get_best_move(&game), Some((square("a1"), square("a8"))));
+        
+        // Taking the knight would win material but leave Black without a move
+        let game = GameState::from_fen("k6n/p1K5/P4B2/8/8/8/6PP/8 w - - 0 1").unwrap();
+        let stalemated = ChessAI::board_after(&game.board, square("f6"), square("h8"));
+        assert_eq!(ai.minimax(&stalemated, 1, -INFINITE_SCORE, INFINITE_SCORE, Color::Black, &mut 0), 0);
+        assert_ne!(ai.get_best_move(&game), Some((square("f6"), square("h8"))));
+    }
+    
+    #[test]
+    fn test_promotes_free_pawn() {
+        let ai = ChessAI::new(Color::White, Difficulty::Intermediate);
+        let square = |name| Position::from_notation(name).unwrap();
+        
+        // Queening is worth more than anything else on the board, the loose knight included
+        let game = GameState::from_fen("7k/P7/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        assert_eq!(ai.get_best_move(&game), Some((square("a7"), square("a8"))));
+        let game = GameState::from_fen("7k/P7/8/4n3/8/8/8/K3R3 w - - 0 1").unwrap();
+        assert_eq!(ai.get_best_move(&game), Some((square("a7"), square("a8"))));
+        
+        let promoted = ChessAI::board_after(&game.board, square("a7"), square("a8"));
+        assert_eq!(promoted.get_piece(&square("a8")), Some(&Piece::new(PieceType::Queen, Color::White)));
+    }
+    
+    #[test]
+    fn test_node_limit_bounds_search() {
+        let mut ai = ChessAI::new(Color::White, Difficulty::Advanced);
+        ai.set_max_nodes(50);
+        let game = GameState::new();
+        
+        let mut nodes = 0;
+        let (from, to) = ai.iterative_deepening(&game, &mut nodes).unwrap();
+        assert!(nodes <= 50, "visited {} nodes", nodes);
+        assert!(game.generate_legal_moves().iter().any(|m| m.from == from && m.to == to));
+        
+        // Even a limit too small to search anything still gives a move
+        ai.set_max_nodes(0);
+        assert!(ai.get_best_move(&game).is_some());
+    }
+    
+    #[test]
+    fn test_self_play_dead_draw_terminates() {
+        let white = ChessAI::new(Color::White, Difficulty::Beginner);
+        let black = ChessAI::new(Color::Black, Difficulty::Beginner);
+        
+        let mut game = GameState::new();
+        game.board = Board::new();
+        game.board.set_piece(Position::new_unchecked(4, 0), Piece::new(PieceType::King, Color::White));
+        game.board.set_piece(Position::new_unchecked(4, 7), Piece::new(PieceType::King, Color::Black));
+        game.refresh_status();
+        
+        let result = self_play(&white, &black, game, 200);
+        assert!(matches!(result, Some(GameResult::Draw(_))), "got {:?}", result);
+    }
+    
+    #[test]
+    fn test_self_play_hopeless_side_resigns() {
+        let white = ChessAI::new(Color::White, Difficulty::Beginner);
+        let black = ChessAI::new(Color::Black, Difficulty::Beginner);
+        
+        // Black to move with a lone king against two queens and a rook
+        let mut game = GameState::new();
+        game.board = Board::new();
+        game.board.set_piece(Position::new_unchecked(4, 0), Piece::new(PieceType::King, Color::White));
+        game.board.set_piece(Position::new_unchecked(0, 0), Piece::new(PieceType::Rook, Color::White));
+        game.board.set_piece(Position::new_unchecked(3, 0), Piece::new(PieceType::Queen, Color::White));
+        game.board.set_piece(Position::new_unchecked(5, 0), Piece::new(PieceType::Queen, Color::White));
+        game.board.set_piece(Position::new_unchecked(4, 7), Piece::new(PieceType::King, Color::Black));
+        game.current_player = Color::Black;
+        game.refresh_status();
+        
+        assert!(black.should_resign(&game));
+        assert!(!white.should_accept_draw(&game));
+        assert_eq!(self_play(&white, &black, game, 200), Some(GameResult::Resignation { winner: Color::White }));
+    }
+    
+    #[test]
+    fn test_evaluation_is_symmetric() {
+        let ai = ChessAI::new(Color::White, Difficulty::Beginner);
+        
+        assert_symmetric(&ai, &Board::new_game());
+        
+        // Unbalanced material with pieces on asymmetric squares
+        let mut board = Board::new_game();
+        board.remove_piece(&Position::new_unchecked(3, 7));
+        board.remove_piece(&Position::new_unchecked(0, 1));
+        board.remove_piece(&Position::new_unchecked(6, 6));
+        board.make_move(&Position::new_unchecked(6, 0), &Position::new_unchecked(5, 2));
+        board.make_move(&Position::new_unchecked(4, 1), &Position::new_unchecked(4, 3));
+        assert_symmetric(&ai, &board);
+        
+        // A sparse endgame
+        let mut endgame = Board::new();
+        endgame.set_piece(Position::new_unchecked(6, 0), Piece::new(PieceType::King, Color::White));
+        endgame.set_piece(Position::new_unchecked(2, 6), Piece::new(PieceType::King, Color::Black));
+        endgame.set_piece(Position::new_unchecked(3, 3), Piece::new(PieceType::Rook, Color::White));
+        endgame.set_piece(Position::new_unchecked(0, 5), Piece::new(PieceType::Pawn, Color::Black));
+        endgame.set_piece(Position::new_unchecked(7, 4), Piece::new(PieceType::Pawn, Color::Black));
+        assert_symmetric(&ai, &endgame);
     }
 }
@@ -0,0 +1,147 @@
+//! Piece-square tables: positional bonuses in centipawns for a piece on each square.
+//!
+//! Each table is laid out as the board looks from White's side, so its first row is
+//! rank 8, and is read mirrored for Black.
+
+use crate::types::{Color, Piece, PieceType, Position};
+
+type Table = [[i32; 8]; 8];
+
+/// Pawns gain as they advance, most of all in the center; the d and e pawns are pushed
+/// early while the wing pawns stay home to shelter a castled king
+const PAWN: Table = [
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [ 50,  50,  50,  50,  50,  50,  50,  50],
+    [ 10,  10,  20,  30,  30,  20,  10,  10],
+    [  5,   5,  10,  25,  25,  10,   5,   5],
+    [  0,   0,   0,  20,  20,   0,   0,   0],
+    [  5,  -5, -10,   0,   0, -10,  -5,   5],
+    [  5,  10,  10, -20, -20,  10,  10,   5],
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+];
+
+/// Knights belong in the center, and are worst in the corners
+const KNIGHT: Table = [
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+    [-40, -20,   0,   0,   0,   0, -20, -40],
+    [-30,   0,  10,  15,  15,  10,   0, -30],
+    [-30,   5,  15,  20,  20,  15,   5, -30],
+    [-30,   0,  15,  20,  20,  15,   0, -30],
+    [-30,   5,  10,  15,  15,  10,   5, -30],
+    [-40, -20,   0,   5,   5,   0, -20, -40],
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+];
+
+/// Bishops avoid the edges and corners
+const BISHOP: Table = [
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+    [-10,   0,   0,   0,   0,   0,   0, -10],
+    [-10,   0,   5,  10,  10,   5,   0, -10],
+    [-10,   5,   5,  10,  10,   5,   5, -10],
+    [-10,   0,  10,  10,  10,  10,   0, -10],
+    [-10,  10,  10,  10,  10,  10,  10, -10],
+    [-10,   5,   0,   0,   0,   0,   5, -10],
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+];
+
+/// Rooks like the seventh rank and the central files
+const ROOK: Table = [
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [  5,  10,  10,  10,  10,  10,  10,   5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [  0,   0,   0,   5,   5,   0,   0,   0],
+];
+
+/// The queen is a little better centralized
+const QUEEN: Table = [
+    [-20, -10, -10,  -5,  -5, -10, -10, -20],
+    [-10,   0,   0,   0,   0,   0,   0, -10],
+    [-10,   0,   5,   5,   5,   5,   0, -10],
+    [ -5,   0,   5,   5,   5,   5,   0,  -5],
+    [  0,   0,   5,   5,   5,   5,   0,  -5],
+    [-10,   5,   5,   5,   5,   5,   0, -10],
+    [-10,   0,   5,   0,   0,   0,   0, -10],
+    [-20, -10, -10,  -5,  -5, -10, -10, -20],
+];
+
+/// With pieces on the board the king hides behind its pawns, castled if it can
+const KING_MIDDLEGAME: Table = [
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-20, -30, -30, -40, -40, -30, -30, -20],
+    [-10, -20, -20, -20, -20, -20, -20, -10],
+    [ 20,  20,   0,   0,   0,   0,  20,  20],
+    [ 20,  30,  10,   0,   0,  10,  30,  20],
+];
+
+/// In the endgame the king comes to the center
+const KING_ENDGAME: Table = [
+    [-50, -40, -30, -20, -20, -30, -40, -50],
+    [-30, -20, -10,   0,   0, -10, -20, -30],
+    [-30, -10,  20,  30,  30,  20, -10, -30],
+    [-30, -10,  30,  40,  40,  30, -10, -30],
+    [-30, -10,  30,  40,  40,  30, -10, -30],
+    [-30, -10,  20,  30,  30,  20, -10, -30],
+    [-30, -30,   0,   0,   0,   0, -30, -30],
+    [-50, -30, -30, -30, -30, -30, -30, -50],
+];
+
+/// Look up the square `pos` in `table` for a piece of `color`
+fn lookup(table: &Table, color: Color, pos: &Position) -> i32 {
+    let row = match color {
+        Color::White => 7 - pos.rank,
+        Color::Black => pos.rank,
+    };
+    table[row as usize][pos.file as usize]
+}
+
+/// The bonus for `piece` on `pos`, except for kings, whose bonus depends on the game phase
+pub fn square_bonus(piece: &Piece, pos: &Position) -> i32 {
+    let table = match piece.piece_type {
+        PieceType::Pawn => &PAWN,
+        PieceType::Knight => &KNIGHT,
+        PieceType::Bishop => &BISHOP,
+        PieceType::Rook => &ROOK,
+        PieceType::Queen => &QUEEN,
+        PieceType::King => return 0,
+    };
+    lookup(table, piece.color, pos)
+}
+
+/// The bonus for a king of `color` on `pos`, blended from the middlegame table with every
+/// piece on the board at a `phase` of 1.0 to the endgame table at 0.0
+pub fn king_bonus(color: Color, pos: &Position, phase: f32) -> i32 {
+    let middlegame = lookup(&KING_MIDDLEGAME, color, pos) as f32;
+    let endgame = lookup(&KING_ENDGAME, color, pos) as f32;
+    (phase * middlegame + (1.0 - phase) * endgame).round() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_tables_mirror_for_black() {
+        let square = |name| Position::from_notation(name).unwrap();
+        let white_knight = Piece::new(PieceType::Knight, Color::White);
+        let black_knight = Piece::new(PieceType::Knight, Color::Black);
+        assert_eq!(square_bonus(&white_knight, &square("f3")), square_bonus(&black_knight, &square("f6")));
+        assert!(square_bonus(&white_knight, &square("f3")) > square_bonus(&white_knight, &square("g1")));
+        
+        // Central pawns gain by advancing two squares, and pawns never count on a back rank
+        let pawn = Piece::new(PieceType::Pawn, Color::White);
+        assert!(square_bonus(&pawn, &square("e4")) > square_bonus(&pawn, &square("e2")));
+        assert_eq!(square_bonus(&pawn, &square("e8")), 0);
+        
+        // A castled king is safest with pieces on, a central king in the endgame
+        assert!(king_bonus(Color::White, &square("g1"), 1.0) > king_bonus(Color::White, &square("e4"), 1.0));
+        assert!(king_bonus(Color::White, &square("e4"), 0.0) > king_bonus(Color::White, &square("g1"), 0.0));
+        assert_eq!(king_bonus(Color::Black, &square("g8"), 0.5), king_bonus(Color::White, &square("g1"), 0.5));
+    }
+}
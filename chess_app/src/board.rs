@@ -1,18 +1,98 @@
 use std;
 use std::collections::HashMap;
+use std::fmt;
 use serde::{Deserialize, Serialize};
+use crate::state::{GameState, Move, SanError, SanParts};
 use crate::types::{Color, Piece, PieceType, Position};
+use crate::zobrist;
+
+/// Reasons a piece layout can't be used for a game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayoutError {
+    /// A piece was placed outside the board
+    InvalidPosition(Position),
+    /// More than one piece was placed on the same square
+    DuplicateSquare(Position),
+    /// A side doesn't have exactly one king
+    KingCount { color: Color, count: usize },
+    /// A pawn stands on the first or last rank
+    PawnOnBackRank(Position),
+    /// The two kings stand next to each other
+    KingsAdjacent,
+    /// A grid square holds a character that is neither a piece letter nor '.'
+    UnknownPiece(char),
+}
+
+/// Reasons the piece placement field of a FEN string can't be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FenError {
+    /// The placement doesn't have exactly 8 ranks separated by '/'; holds the number found
+    WrongRankCount(usize),
+    /// A rank's pieces and empty squares don't add up to 8 files; `rank` counts from 0 for
+    /// rank 1, like `Position::rank`
+    BadFileSum { rank: u8 },
+    /// A character is neither a digit nor one of "pnbrqkPNBRQK"
+    UnknownPiece(char),
+}
+
+/// Reasons a pawn can't be promoted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromotionError {
+    /// There is no pawn on the square
+    NoPawn,
+    /// The pawn hasn't reached the last rank
+    NotOnLastRank,
+    /// Pawns can't promote to a king or stay a pawn
+    InvalidPiece(PieceType),
+}
+
+/// How a king is put in check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckType {
+    /// The piece that moved gives check
+    Direct,
+    /// Moving a piece out of the way let another piece give check
+    Discovered,
+    /// Two pieces give check at once, so only a king move can answer it
+    Double,
+}
 
 /// Represents a chess board.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "SavedBoard")]
 pub struct Board {
+    #[serde(with = "crate::types::position_map")]
     pieces: HashMap<Position, Piece>,
+    // Zobrist hash of the pieces, kept up to date as they are set and removed; like the king
+    // squares it isn't saved, since `SavedBoard` rebuilds it from the pieces on load
+    #[serde(skip)]
+    hash: u64,
+    // Square of each side's king by `Color::index`, kept up to date like the hash
+    #[serde(skip)]
+    kings: [Option<Position>; 2],
+}
+
+/// The fields of a saved board, from which the rest are rebuilt on load.
+#[derive(Deserialize)]
+struct SavedBoard {
+    #[serde(with = "crate::types::position_map")]
+    pieces: HashMap<Position, Piece>,
+}
+
+impl From<SavedBoard> for Board {
+    fn from(saved: SavedBoard) -> Self {
+        let mut board = Board::new();
+        for (pos, piece) in saved.pieces {
+            board.set_piece(pos, piece);
+        }
+        board
+    }
 }
 
 impl Board {
     /// Creates an empty chess board.
     pub fn new() -> Self {
-        Board { pieces: HashMap::new() }
+        Board { pieces: HashMap::new(), hash: 0, kings: [None; 2] }
     }
 
     /// Creates a new chess board with assets in standard starting positions.
@@ -21,8 +101,8 @@ impl Board {
         
         // Place pawns
         for file in 0..8 {
-            board.set_piece(Position::new(file, 1), Piece::new(PieceType::Pawn, Color::White));
-            board.set_piece(Position::new(file, 6), Piece::new(PieceType::Pawn, Color::Black));
+            board.set_piece(Position::new_unchecked(file, 1), Piece::new(PieceType::Pawn, Color::White));
+            board.set_piece(Position::new_unchecked(file, 6), Piece::new(PieceType::Pawn, Color::Black));
         }
         
         // Place the assets on the back ranks
@@ -32,26 +112,324 @@ impl Board {
         ];
         
         for (file, &piece_type) in pieces.iter().enumerate() {
-            board.set_piece(Position::new(file as u8, 0), Piece::new(piece_type, Color::White));
-            board.set_piece(Position::new(file as u8, 7), Piece::new(piece_type, Color::Black));
+            board.set_piece(Position::new_unchecked(file as u8, 0), Piece::new(piece_type, Color::White));
+            board.set_piece(Position::new_unchecked(file as u8, 7), Piece::new(piece_type, Color::Black));
         }
         
         board
     }
 
+    /// Creates a board from a list of placements, checking that the result is a legal layout.
+    pub fn try_from_placements(placements: &[(Position, Piece)]) -> Result<Self, LayoutError> {
+        let mut board = Self::new();
+        
+        for &(pos, piece) in placements {
+            if !pos.is_valid() {
+                return Err(LayoutError::InvalidPosition(pos));
+            }
+            if board.get_piece(&pos).is_some() {
+                return Err(LayoutError::DuplicateSquare(pos));
+            }
+            board.set_piece(pos, piece);
+        }
+        
+        board.validate_legal_layout()?;
+        Ok(board)
+    }
+
+    /// Creates a board from a grid of FEN piece letters, with '.' for empty squares, checking
+    /// that the result is a legal layout.
+    ///
+    /// `rows[0]` is rank 8 and each row runs from file a to file h, so the grid reads like a
+    /// diagram from White's side.
+    pub fn from_grid(rows: [[char; 8]; 8]) -> Result<Self, LayoutError> {
+        let mut placements = Vec::new();
+        
+        for (i, row) in rows.iter().enumerate() {
+            for (file, &c) in row.iter().enumerate() {
+                if c == '.' {
+                    continue;
+                }
+                let piece = Piece::from_fen_char(c).ok_or(LayoutError::UnknownPiece(c))?;
+                placements.push((Position::new_unchecked(file as u8, 7 - i as u8), piece));
+            }
+        }
+        
+        Self::try_from_placements(&placements)
+    }
+
+    /// Checks that the board could arise in a game: one king per side, kings apart,
+    /// and no pawns on the first or last rank.
+    pub fn validate_legal_layout(&self) -> Result<(), LayoutError> {
+        for color in [Color::White, Color::Black] {
+            let count = self.pieces.values()
+                .filter(|p| p.piece_type == PieceType::King && p.color == color)
+                .count();
+            if count != 1 {
+                return Err(LayoutError::KingCount { color, count });
+            }
+        }
+        
+        for (pos, piece) in &self.pieces {
+            if !pos.is_valid() {
+                return Err(LayoutError::InvalidPosition(*pos));
+            }
+            if piece.piece_type == PieceType::Pawn && (pos.rank == 0 || pos.rank == 7) {
+                return Err(LayoutError::PawnOnBackRank(*pos));
+            }
+        }
+        
+        if let (Some(white), Some(black)) = (self.find_king(Color::White), self.find_king(Color::Black)) {
+            let file_diff = (white.file as i8 - black.file as i8).abs();
+            let rank_diff = (white.rank as i8 - black.rank as i8).abs();
+            if file_diff <= 1 && rank_diff <= 1 {
+                return Err(LayoutError::KingsAdjacent);
+            }
+        }
+        
+        Ok(())
+    }
+
+    /// Creates a board from the piece placement field of a FEN string.
+    ///
+    /// Any fields after the placement, such as the side to move and castling rights, are
+    /// ignored rather than checked.
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        let placement = fen.split_whitespace().next().unwrap_or("");
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::WrongRankCount(ranks.len()));
+        }
+        
+        let mut board = Self::new();
+        for (i, rank_str) in ranks.iter().enumerate() {
+            let rank = 7 - i as u8;
+            let mut file = 0;
+            
+            for c in rank_str.chars() {
+                if let Some(empty) = c.to_digit(10) {
+                    file += empty as u8;
+                } else {
+                    let piece = Piece::from_fen_char(c).ok_or(FenError::UnknownPiece(c))?;
+                    if file > 7 {
+                        return Err(FenError::BadFileSum { rank });
+                    }
+                    board.set_piece(Position::new_unchecked(file, rank), piece);
+                    file += 1;
+                }
+                
+                // Stop before a run of digits can overflow the count
+                if file > 8 {
+                    return Err(FenError::BadFileSum { rank });
+                }
+            }
+            
+            if file != 8 {
+                return Err(FenError::BadFileSum { rank });
+            }
+        }
+        
+        Ok(board)
+    }
+
+    /// Returns the piece placement field of the FEN string for this board.
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.get_piece(&Position::new_unchecked(file, rank)) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(piece.to_fen_char());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+        
+        fen
+    }
+
+    /// Returns a text diagram of the board with rank and file labels.
+    ///
+    /// With `Color::White` rank 8 is at the top and file a on the left; with `Color::Black`
+    /// the board is seen from Black's side, with rank 1 at the top and file a on the right.
+    /// Empty squares are shown as `.`.
+    pub fn to_ascii(&self, orientation: Color) -> String {
+        self.diagram(orientation, |piece| piece.map_or('.', |p| p.to_fen_char()).to_string())
+    }
+
+    /// Returns a text diagram like `to_ascii`, but with Unicode chess glyphs for the pieces.
+    ///
+    /// Unlike `to_unicode` it has no color codes, so it reads the same in test failure
+    /// messages and logs as in a terminal.
+    pub fn to_unicode_string(&self, orientation: Color) -> String {
+        self.diagram(orientation, |piece| piece.map_or(".", |p| p.unicode_symbol()).to_string())
+    }
+
+    /// Lays out the squares as `symbol` draws them, one rank per line, with rank and file labels
+    fn diagram(&self, orientation: Color, symbol: impl Fn(Option<&Piece>) -> String) -> String {
+        let (ranks, files): (Vec<u8>, Vec<u8>) = match orientation {
+            Color::White => ((0..8).rev().collect(), (0..8).collect()),
+            Color::Black => ((0..8).collect(), (0..8).rev().collect()),
+        };
+        
+        let mut out = String::new();
+        for &rank in &ranks {
+            out.push_str(&(rank + 1).to_string());
+            for &file in &files {
+                out.push(' ');
+                out.push_str(&symbol(self.get_piece(&Position::new_unchecked(file, rank))));
+            }
+            out.push('\n');
+        }
+        
+        out.push(' ');
+        for &file in &files {
+            out.push(' ');
+            out.push((b'a' + file) as char);
+        }
+        out.push('\n');
+        
+        out
+    }
+
+    /// Returns the board drawn with Unicode chess glyphs on ANSI-shaded squares, for terminals,
+    /// oriented and labelled like `to_ascii`.
+    pub fn to_unicode(&self, orientation: Color) -> String {
+        const LIGHT_SQUARE: &str = "\x1b[48;5;180m";
+        const DARK_SQUARE: &str = "\x1b[48;5;94m";
+        const RESET: &str = "\x1b[0m";
+
+        let (ranks, files): (Vec<u8>, Vec<u8>) = match orientation {
+            Color::White => ((0..8).rev().collect(), (0..8).collect()),
+            Color::Black => ((0..8).collect(), (0..8).rev().collect()),
+        };
+        
+        let mut out = String::new();
+        for &rank in &ranks {
+            out.push_str(&(rank + 1).to_string());
+            out.push(' ');
+            for &file in &files {
+                let pos = Position::new_unchecked(file, rank);
+                let background = match pos.square_color() {
+                    Color::White => LIGHT_SQUARE,
+                    Color::Black => DARK_SQUARE,
+                };
+                let symbol = self.get_piece(&pos).map_or(" ", |p| p.unicode_symbol());
+                out.push_str(&format!("{} {} {}", background, symbol, RESET));
+            }
+            out.push('\n');
+        }
+        
+        out.push(' ');
+        for &file in &files {
+            out.push_str("  ");
+            out.push((b'a' + file) as char);
+        }
+        out.push('\n');
+        
+        out
+    }
+
     /// Returns a reference to the piece at the given position, if any.
     pub fn get_piece(&self, pos: &Position) -> Option<&Piece> {
         self.pieces.get(pos)
     }
 
+    /// Returns every square of the board, rank by rank from a1 to h8.
+    pub fn iter_squares() -> impl Iterator<Item = Position> {
+        (0..8).flat_map(|rank| (0..8).map(move |file| Position::new_unchecked(file, rank)))
+    }
+
+    /// Returns the occupied squares with their pieces, in the same order as `iter_squares`.
+    pub fn iter_pieces(&self) -> impl Iterator<Item = (Position, &Piece)> + '_ {
+        Self::iter_squares().filter_map(|pos| self.get_piece(&pos).map(|piece| (pos, piece)))
+    }
+
     /// Places a piece at the given position, replacing any existing piece.
     pub fn set_piece(&mut self, pos: Position, piece: Piece) {
-        self.pieces.insert(pos, piece);
+        if let Some(replaced) = self.pieces.insert(pos, piece) {
+            self.hash ^= zobrist::piece_key(&replaced, &pos);
+            self.forget_king(&replaced, &pos);
+        }
+        self.hash ^= zobrist::piece_key(&piece, &pos);
+        if piece.piece_type == PieceType::King {
+            self.kings[piece.color.index()] = Some(pos);
+        }
     }
 
     /// Removes and returns the piece at the given position, if any.
     pub fn remove_piece(&mut self, pos: &Position) -> Option<Piece> {
-        self.pieces.remove(pos)
+        let removed = self.pieces.remove(pos)?;
+        self.hash ^= zobrist::piece_key(&removed, pos);
+        self.forget_king(&removed, pos);
+        Some(removed)
+    }
+
+    /// Updates the king cache for `piece` having left `pos`
+    fn forget_king(&mut self, piece: &Piece, pos: &Position) {
+        if piece.piece_type != PieceType::King || self.kings[piece.color.index()] != Some(*pos) {
+            return;
+        }
+        
+        // Only boards set up with extra kings have another one to fall back on
+        self.kings[piece.color.index()] = self.pieces.iter()
+            .find(|(_, other)| *other == piece)
+            .map(|(other_pos, _)| *other_pos);
+    }
+
+    /// Returns the squares where this board and `other` hold different pieces, in
+    /// rank-then-file order from a1.
+    pub fn diff(&self, other: &Board) -> Vec<Position> {
+        (0..8)
+            .flat_map(|rank| (0..8).map(move |file| Position::new_unchecked(file, rank)))
+            .filter(|pos| self.get_piece(pos) != other.get_piece(pos))
+            .collect()
+    }
+
+    /// Returns the Zobrist hash of the pieces, maintained as they move.
+    pub fn piece_hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Computes the Zobrist hash of the pieces and the side to move from scratch.
+    pub fn zobrist_hash(&self, side_to_move: Color) -> u64 {
+        self.pieces.iter()
+            .fold(zobrist::side_to_move_key(side_to_move), |hash, (pos, piece)| {
+                hash ^ zobrist::piece_key(piece, pos)
+            })
+    }
+
+    /// Replaces a pawn that has reached the last rank with the chosen piece.
+    pub fn promote_pawn(&mut self, pos: Position, to: PieceType) -> Result<(), PromotionError> {
+        let pawn = match self.get_piece(&pos) {
+            Some(piece) if piece.piece_type == PieceType::Pawn => *piece,
+            _ => return Err(PromotionError::NoPawn),
+        };
+        
+        let last_rank = if pawn.color == Color::White { 7 } else { 0 };
+        if pos.rank != last_rank {
+            return Err(PromotionError::NotOnLastRank);
+        }
+        
+        if matches!(to, PieceType::King | PieceType::Pawn) {
+            return Err(PromotionError::InvalidPiece(to));
+        }
+        
+        self.set_piece(pos, Piece::new(to, pawn.color));
+        Ok(())
     }
 
     /// Returns true if the board has no assets.
@@ -59,59 +437,104 @@ impl Board {
         self.pieces.is_empty()
     }
 
+    /// Returns the number of assets on the board.
+    pub fn piece_count(&self) -> usize {
+        self.pieces.len()
+    }
+
+    /// Returns the number of assets of one color on the board.
+    pub fn count_pieces_by_color(&self, color: Color) -> usize {
+        self.pieces.values().filter(|piece| piece.color == color).count()
+    }
+
+    /// Removes every piece of one color and type, e.g. for a handicap game at queen odds.
+    /// Returns the number of pieces removed.
+    pub fn remove_all_of(&mut self, color: Color, piece_type: PieceType) -> usize {
+        let target = Piece::new(piece_type, color);
+        let squares: Vec<Position> = self.pieces.iter()
+            .filter(|(_, piece)| **piece == target)
+            .map(|(pos, _)| *pos)
+            .collect();
+
+        for pos in &squares {
+            self.remove_piece(pos);
+        }
+        squares.len()
+    }
+
     /// Find the position of the king for the given color
+    ///
+    /// Kings are tracked as they are set and removed, so this is a lookup rather than a scan.
     pub fn find_king(&self, color: Color) -> Option<Position> {
-        for rank in 0..8 {
-            for file in 0..8 {
-                let pos = Position::new(file, rank);
-                if let Some(piece) = self.get_piece(&pos) {
-                    if piece.piece_type == PieceType::King && piece.color == color {
-                        return Some(pos);
-                    }
-                }
-            }
-        }
-        None
+        self.kings[color.index()]
+    }
+
+    /// Scan the pieces for the king of the given color
+    ///
+    /// Finds the same square as `find_king` without relying on its cache. On a board set
+    /// up with more than one king of a color, any of them may be returned.
+    pub fn king_position(&self, color: Color) -> Option<Position> {
+        let king = Piece::new(PieceType::King, color);
+        self.pieces.iter()
+            .find(|(_, piece)| **piece == king)
+            .map(|(pos, _)| *pos)
     }
 
     /// Check if a position is under attack by a specific color
     pub fn is_square_attacked(&self, pos: &Position, by_color: Color) -> bool {
         // Check all opponent's pieces for potential attacks
-        for rank in 0..8 {
-            for file in 0..8 {
-                let from = Position::new(file, rank);
-                if let Some(piece) = self.get_piece(&from) {
-                    if piece.color == by_color {
-                        // Special case for pawns, since their attack pattern is different from their move pattern
-                        if piece.piece_type == PieceType::Pawn {
-                            let direction = if by_color == Color::White { 1 } else { -1 };
-                            let file_diff = (from.file as i8 - pos.file as i8).abs();
-                            let rank_diff = pos.rank as i8 - from.rank as i8;
-                            
-                            // Pawns attack diagonally forward
-                            if file_diff == 1 && rank_diff == direction {
-                                return true;
-                            }
-                        } 
-                        // For king, we need special handling to avoid infinite recursion
-                        else if piece.piece_type == PieceType::King {
-                            let file_diff = (from.file as i8 - pos.file as i8).abs();
-                            let rank_diff = (from.rank as i8 - pos.rank as i8).abs();
-                            
-                            // King can attack one square in any direction
-                            if file_diff <= 1 && rank_diff <= 1 {
-                                return true;
-                            }
-                        }
-                        // For all other pieces, use the regular move validation
-                        else if self.is_valid_piece_move(&from, pos, piece) {
-                            return true;
-                        }
-                    }
-                }
+        self.iter_pieces()
+            .any(|(from, piece)| piece.color == by_color && self.piece_attacks(&from, piece, pos))
+    }
+
+    /// Returns the positions of the enemy pieces giving check to the king of `color`
+    pub fn checkers(&self, color: Color) -> Vec<Position> {
+        let king_pos = match self.find_king(color) {
+            Some(pos) => pos,
+            None => return Vec::new(),
+        };
+
+        self.iter_pieces()
+            .filter(|(from, piece)| piece.color != color && self.piece_attacks(from, piece, &king_pos))
+            .map(|(from, _)| from)
+            .collect()
+    }
+
+    /// Classifies the check on the king of `color`, given the square the last move landed on
+    ///
+    /// A single checker on `moved_to` is a direct check; one elsewhere was uncovered by
+    /// the moving piece, so the check is discovered.
+    pub fn check_type(&self, color: Color, moved_to: &Position) -> Option<CheckType> {
+        match self.checkers(color).as_slice() {
+            [] => None,
+            [checker] if checker == moved_to => Some(CheckType::Direct),
+            [_] => Some(CheckType::Discovered),
+            _ => Some(CheckType::Double),
+        }
+    }
+
+    /// Checks if the piece on `from` attacks `pos`, whether or not it could legally move there
+    fn piece_attacks(&self, from: &Position, piece: &Piece, pos: &Position) -> bool {
+        let file_diff = (from.file as i8 - pos.file as i8).abs();
+        match piece.piece_type {
+            // Special case for pawns, since their attack pattern is different from their move pattern
+            PieceType::Pawn => {
+                let direction = if piece.color == Color::White { 1 } else { -1 };
+                let rank_diff = pos.rank as i8 - from.rank as i8;
+
+                // Pawns attack diagonally forward
+                file_diff == 1 && rank_diff == direction
+            }
+            // For king, we need special handling to avoid infinite recursion
+            PieceType::King => {
+                let rank_diff = (from.rank as i8 - pos.rank as i8).abs();
+
+                // King can attack one square in any direction
+                file_diff <= 1 && rank_diff <= 1 && from != pos
             }
+            // For all other pieces, use the regular move validation
+            _ => self.is_valid_piece_move(from, pos, piece),
         }
-        false
     }
 
     /// Check if the king of a specific color is in check
@@ -122,6 +545,153 @@ impl Board {
         false
     }
 
+    /// Sum the weights of the enemy pieces attacking any square next to the king of `color`
+    ///
+    /// Each attacker counts once, weighted by how dangerous it is near a king: 1 for a pawn,
+    /// 2 for a minor piece, 3 for a rook and 5 for a queen. The enemy king isn't counted.
+    pub fn attack_weight_near_king(&self, color: Color) -> i32 {
+        let king_pos = match self.find_king(color) {
+            Some(pos) => pos,
+            None => return 0,
+        };
+        let zone: Vec<Position> = king_pos.king_neighbors().collect();
+        
+        self.pieces.iter()
+            .filter(|(_, piece)| piece.color != color)
+            .filter(|&(from, piece)| zone.iter().any(|pos| pos != from && self.piece_attacks(from, piece, pos)))
+            .map(|(_, piece)| match piece.piece_type {
+                PieceType::Pawn => 1,
+                PieceType::Knight | PieceType::Bishop => 2,
+                PieceType::Rook => 3,
+                PieceType::Queen => 5,
+                PieceType::King => 0,
+            })
+            .sum()
+    }
+
+    /// Check if `color` has bishops on both light and dark squares
+    pub fn has_bishop_pair(&self, color: Color) -> bool {
+        let bishop = Piece::new(PieceType::Bishop, color);
+        let mut square_colors = self.pieces.iter()
+            .filter(|(_, piece)| **piece == bishop)
+            .map(|(pos, _)| pos.square_color());
+
+        match square_colors.next() {
+            Some(first) => square_colors.any(|other| other != first),
+            None => false,
+        }
+    }
+
+    /// Check if neither side has enough material left to deliver checkmate
+    ///
+    /// Covers bare kings, a single minor piece against a bare king, and bishops
+    /// standing on squares of the same color.
+    pub fn is_insufficient_material(&self) -> bool {
+        let mut minor_pieces = Vec::new();
+        
+        for (pos, piece) in &self.pieces {
+            match piece.piece_type {
+                PieceType::King => {}
+                PieceType::Bishop | PieceType::Knight => minor_pieces.push((pos, piece)),
+                _ => return false,
+            }
+        }
+        
+        match minor_pieces.len() {
+            0 | 1 => true,
+            // Bishops all on the same square color can never cover the other color
+            _ => minor_pieces.iter().all(|(_, piece)| piece.piece_type == PieceType::Bishop)
+                && minor_pieces.windows(2).all(|pair| pair[0].0.square_color() == pair[1].0.square_color()),
+        }
+    }
+
+    /// Check if no sequence of legal moves can lead to checkmate
+    ///
+    /// Besides insufficient material, this detects positions where the only pieces left
+    /// are kings and pawns that are locked head-on, where no pawn can capture and no king
+    /// can reach an enemy pawn it could take, so nothing but the kings can ever move.
+    pub fn is_dead_position(&self) -> bool {
+        if self.is_insufficient_material() {
+            return true;
+        }
+        
+        let mut has_pawns = false;
+        for (pos, piece) in &self.pieces {
+            match piece.piece_type {
+                PieceType::King => continue,
+                PieceType::Pawn => has_pawns = true,
+                _ => return false,
+            }
+            
+            let direction = if piece.color == Color::White { 1 } else { -1 };
+            
+            // Every pawn must be blocked by another pawn
+            let ahead = match pos.offset(0, direction) {
+                Some(ahead) => ahead,
+                None => return false,
+            };
+            if !matches!(self.get_piece(&ahead), Some(p) if p.piece_type == PieceType::Pawn) {
+                return false;
+            }
+            
+            // ...and have nothing to capture
+            for file_step in [-1, 1] {
+                if let Some(target) = pos.offset(file_step, direction) {
+                    if matches!(self.get_piece(&target), Some(p) if p.color != piece.color) {
+                        return false;
+                    }
+                }
+            }
+        }
+        
+        has_pawns
+            && !self.king_can_break_pawn_wall(Color::White)
+            && !self.king_can_break_pawn_wall(Color::Black)
+    }
+
+    /// Check if the king of the given color can walk to an enemy pawn that it can capture,
+    /// moving only over empty squares not attacked by enemy pawns
+    fn king_can_break_pawn_wall(&self, color: Color) -> bool {
+        let start = match self.find_king(color) {
+            Some(pos) => pos,
+            None => return false,
+        };
+        
+        let attacked_by_enemy_pawn = |pos: &Position| {
+            let direction = if color == Color::White { 1 } else { -1 };
+            [-1, 1].iter().any(|&file_step| {
+                pos.offset(file_step, direction).is_some_and(|square| {
+                    self.get_piece(&square) == Some(&Piece::new(PieceType::Pawn, color.opposite()))
+                })
+            })
+        };
+        
+        let mut visited = vec![start];
+        let mut frontier = vec![start];
+        
+        while let Some(pos) = frontier.pop() {
+            for next in pos.king_neighbors() {
+                if visited.contains(&next) || attacked_by_enemy_pawn(&next) {
+                    continue;
+                }
+                
+                match self.get_piece(&next) {
+                    Some(piece) if piece.piece_type == PieceType::Pawn => {
+                        if piece.color != color {
+                            return true;
+                        }
+                    }
+                    _ => {
+                        visited.push(next);
+                        frontier.push(next);
+                    }
+                }
+            }
+        }
+        
+        false
+    }
+
     /// Makes a move without validation (for internal use)
     fn make_move_unchecked(&mut self, from: &Position, to: &Position) {
         if let Some(piece) = self.remove_piece(from) {
@@ -157,6 +727,114 @@ impl Board {
         !board_copy.is_king_in_check(piece.color)
     }
 
+    /// Validates a move like `is_valid_move`, additionally rejecting moves of pieces
+    /// that don't belong to the side to move
+    pub fn is_valid_move_for(&self, from: &Position, to: &Position, side_to_move: Color) -> bool {
+        match self.get_piece(from) {
+            Some(piece) if piece.color == side_to_move => self.is_valid_move(from, to),
+            _ => false,
+        }
+    }
+
+    /// Checks if moving the piece on `from` to `to` is a pawn reaching its last rank,
+    /// which is only complete with a promotion piece
+    pub fn requires_promotion(&self, from: &Position, to: &Position) -> bool {
+        match self.get_piece(from) {
+            Some(piece) if piece.piece_type == PieceType::Pawn => {
+                let last_rank = if piece.color == Color::White { 7 } else { 0 };
+                to.rank == last_rank
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks if moving the piece on `from` to `to` is a king stepping two files from its
+    /// starting square along the home rank, which is how castling is written
+    pub fn is_castling_move(&self, from: &Position, to: &Position) -> bool {
+        match self.get_piece(from) {
+            Some(piece) if piece.piece_type == PieceType::King => {
+                let home_rank = if piece.color == Color::White { 0 } else { 7 };
+                from.file == 4 && from.rank == home_rank && to.rank == home_rank && (to.file == 2 || to.file == 6)
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks the board conditions for castling the king on `from` to `to`: the rook is on its
+    /// corner, the squares between them are empty, and the king is not in check and doesn't
+    /// pass through or land on an attacked square.
+    ///
+    /// Castling rights depend on the game's history, so checking them is left to the caller.
+    pub fn can_castle(&self, from: &Position, to: &Position) -> bool {
+        if !self.is_castling_move(from, to) {
+            return false;
+        }
+        let color = match self.get_piece(from) {
+            Some(piece) => piece.color,
+            None => return false,
+        };
+
+        let (rook_from, _) = Self::castling_rook_squares(from, to);
+        if self.get_piece(&rook_from) != Some(&Piece::new(PieceType::Rook, color)) || !self.is_path_clear(from, &rook_from) {
+            return false;
+        }
+
+        let step = if to.file > from.file { 1 } else { -1 };
+        (0..=2).all(|i| {
+            let crossed = Position::new_unchecked((from.file as i8 + step * i) as u8, from.rank);
+            !self.is_square_attacked(&crossed, color.opposite())
+        })
+    }
+
+    /// Castles the king on `from` to `to`, moving the rook over it.
+    /// Returns true if castling was possible on the board and executed, false otherwise.
+    pub fn castle(&mut self, from: &Position, to: &Position) -> bool {
+        if !self.can_castle(from, to) {
+            return false;
+        }
+
+        let (rook_from, rook_to) = Self::castling_rook_squares(from, to);
+        self.make_move_unchecked(from, to);
+        self.make_move_unchecked(&rook_from, &rook_to);
+        true
+    }
+
+    /// Captures en passant with the pawn on `from`, moving it diagonally forward onto the
+    /// empty square `to` and removing the enemy pawn beside it.
+    /// Returns true if the capture was possible on the board and executed, false otherwise.
+    ///
+    /// Whether the enemy pawn has just made its double step depends on the game's history,
+    /// so checking that is left to the caller, like king safety.
+    pub fn capture_en_passant(&mut self, from: &Position, to: &Position) -> bool {
+        let pawn = match self.get_piece(from) {
+            Some(piece) if piece.piece_type == PieceType::Pawn => *piece,
+            _ => return false,
+        };
+
+        let forward = if pawn.color == Color::White { 1 } else { -1 };
+        let is_diagonal_step = (to.file as i8 - from.file as i8).abs() == 1 && to.rank as i8 - from.rank as i8 == forward;
+        let captured = Position::new_unchecked(to.file, from.rank);
+        if !is_diagonal_step
+            || self.get_piece(to).is_some()
+            || self.get_piece(&captured) != Some(&Piece::new(PieceType::Pawn, pawn.color.opposite()))
+        {
+            return false;
+        }
+
+        self.remove_piece(&captured);
+        self.make_move_unchecked(from, to);
+        true
+    }
+
+    /// The rook's squares before and after castling the king from `from` to `to`
+    fn castling_rook_squares(from: &Position, to: &Position) -> (Position, Position) {
+        if to.file > from.file {
+            (Position::new_unchecked(7, from.rank), Position::new_unchecked(5, from.rank))
+        } else {
+            (Position::new_unchecked(0, from.rank), Position::new_unchecked(3, from.rank))
+        }
+    }
+
     /// Validates if a move is legal according to the specific piece rules, without check validation
     fn is_valid_piece_move(&self, from: &Position, to: &Position, piece: &Piece) -> bool {
         match piece.piece_type {
@@ -187,31 +865,253 @@ impl Board {
             return false;
         }
 
+        #[cfg(debug_assertions)]
+        let before = self.clone();
+
         if let Some(piece) = self.remove_piece(from) {
             self.set_piece(*to, piece);
+            #[cfg(debug_assertions)]
+            Board::assert_move_invariants(&before, self, from, to);
             true
         } else {
             false
         }
     }
 
+    /// Panics unless `after` could result from moving the piece on `from` to `to` in `before`:
+    /// the piece lands on `to` (promoted if it's a pawn), at most one piece is captured,
+    /// and each side keeps its kings.
+    ///
+    /// Only compiled with debug assertions, to catch move-application bugs early.
+    #[cfg(debug_assertions)]
+    pub(crate) fn assert_move_invariants(before: &Board, after: &Board, from: &Position, to: &Position) {
+        let moved = before.get_piece(from)
+            .unwrap_or_else(|| panic!("no piece on {} to move", from.to_notation()));
+        let landed = after.get_piece(to)
+            .unwrap_or_else(|| panic!("moving piece missing from {}", to.to_notation()));
+        assert_eq!(landed.color, moved.color, "wrong color on {}", to.to_notation());
+        assert!(
+            landed.piece_type == moved.piece_type || moved.piece_type == PieceType::Pawn,
+            "{:?} turned into {:?}", moved.piece_type, landed.piece_type
+        );
+
+        let (count_before, count_after) = (before.piece_count(), after.piece_count());
+        assert!(
+            count_after <= count_before && count_before - count_after <= 1,
+            "piece count changed from {} to {}", count_before, count_after
+        );
+
+        for color in [Color::White, Color::Black] {
+            let kings = |board: &Board| board.pieces.values()
+                .filter(|p| p.piece_type == PieceType::King && p.color == color)
+                .count();
+            assert_eq!(kings(after), kings(before), "{:?} king count changed", color);
+        }
+    }
+
     /// Returns all valid moves for a piece at the given position.
     pub fn get_valid_moves(&self, pos: &Position) -> Vec<Position> {
         let mut valid_moves = Vec::new();
         
-        if let Some(_piece) = self.get_piece(pos) {
-            // Check all possible destination squares
-            for rank in 0..8 {
-                for file in 0..8 {
-                    let dest = Position::new(file, rank);
-                    if self.is_valid_move(pos, &dest) {
-                        valid_moves.push(dest);
+        let piece = match self.get_piece(pos) {
+            Some(p) => p,
+            None => return valid_moves,
+        };
+        
+        // Only king moves, pinned pieces and moves made while in check can expose the king,
+        // so every other move can skip the clone-and-check in is_valid_move
+        let needs_king_safety_check = piece.piece_type == PieceType::King
+            || self.is_king_in_check(piece.color)
+            || self.pinned_pieces(piece.color).iter().any(|(pinned, _)| pinned == pos);
+        
+        // Check all possible destination squares
+        for dest in Self::iter_squares() {
+            let is_valid = if needs_king_safety_check {
+                self.is_valid_move(pos, &dest)
+            } else {
+                self.get_piece(&dest).is_none_or(|target| target.color != piece.color)
+                    && self.is_valid_piece_move(pos, &dest, piece)
+            };
+            
+            if is_valid {
+                valid_moves.push(dest);
+            }
+        }
+        
+        valid_moves
+    }
+    
+    /// Returns the moves of `color` that uncover an attack on an enemy piece by a bishop,
+    /// rook or queen standing behind the moving piece, as `(from, to)` squares
+    pub fn discovered_attack_moves(&self, color: Color) -> Vec<(Position, Position)> {
+        let mut moves = Vec::new();
+        
+        for rank in 0..8 {
+            for file in 0..8 {
+                let slider_pos = Position::new_unchecked(file, rank);
+                let directions: &[(i8, i8)] = match self.get_piece(&slider_pos) {
+                    Some(piece) if piece.color == color => match piece.piece_type {
+                        PieceType::Bishop => &[(1, 1), (1, -1), (-1, 1), (-1, -1)],
+                        PieceType::Rook => &[(0, 1), (0, -1), (1, 0), (-1, 0)],
+                        PieceType::Queen => &[
+                            (0, 1), (0, -1), (1, 0), (-1, 0),
+                            (1, 1), (1, -1), (-1, 1), (-1, -1),
+                        ],
+                        _ => continue,
+                    },
+                    _ => continue,
+                };
+                
+                for &(file_step, rank_step) in directions {
+                    // Walk the ray: one friendly blocker, then an enemy piece behind it
+                    let mut blocker = None;
+                    let mut between = Vec::new();
+                    
+                    for pos in slider_pos.ray(file_step, rank_step) {
+                        match self.get_piece(&pos) {
+                            None => between.push(pos),
+                            Some(piece) if piece.color == color && blocker.is_none() => blocker = Some(pos),
+                            Some(piece) => {
+                                if let (Some(from), true) = (blocker, piece.color != color) {
+                                    // Moves along the ray keep blocking it
+                                    for to in self.get_valid_moves(&from) {
+                                        if !between.contains(&to) && !moves.contains(&(from, to)) {
+                                            moves.push((from, to));
+                                        }
+                                    }
+                                }
+                                break;
+                            }
+                        }
                     }
                 }
             }
         }
         
-        valid_moves
+        moves
+    }
+    
+    /// Returns the pieces of the given color pinned to their king, each paired with the
+    /// position of the enemy piece pinning it
+    pub fn pinned_pieces(&self, color: Color) -> Vec<(Position, Position)> {
+        let mut pins = Vec::new();
+        
+        let king_pos = match self.find_king(color) {
+            Some(pos) => pos,
+            None => return pins,
+        };
+        
+        let directions: [(i8, i8); 8] = [
+            (0, 1), (0, -1), (1, 0), (-1, 0),
+            (1, 1), (1, -1), (-1, 1), (-1, -1),
+        ];
+        
+        for (file_step, rank_step) in directions {
+            let is_diagonal = file_step != 0 && rank_step != 0;
+            let mut candidate = None;
+            
+            for pos in king_pos.ray(file_step, rank_step) {
+                if let Some(piece) = self.get_piece(&pos) {
+                    if piece.color == color {
+                        // A second friendly piece on the ray means nothing is pinned
+                        if candidate.is_some() {
+                            break;
+                        }
+                        candidate = Some(pos);
+                    } else {
+                        let pins_along_ray = match piece.piece_type {
+                            PieceType::Queen => true,
+                            PieceType::Bishop => is_diagonal,
+                            PieceType::Rook => !is_diagonal,
+                            _ => false,
+                        };
+                        
+                        if let (Some(pinned), true) = (candidate, pins_along_ray) {
+                            pins.push((pinned, pos));
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+        
+        pins
+    }
+    
+    /// Writes `mv` in standard algebraic notation for the side whose piece stands on
+    /// `mv.from`, e.g. "Nf3", "Nbd7", "exd5", "O-O" or "e8=Q#", naming the origin file or
+    /// rank when another piece of the same type could also reach the target.
+    ///
+    /// Only the board is consulted, so the move is taken to be legal in its game: castling
+    /// is allowed whenever the king and rook stand on their squares, a pawn stepping
+    /// diagonally onto an empty square is an en passant capture, and a promotion without a
+    /// piece is to a queen. The move is then written by `GameState::to_san`, and one from an
+    /// empty square, or that is illegal even so, is written as coordinates.
+    pub fn move_to_san(&self, mv: &Move) -> String {
+        let coordinates = || format!("{}{}", mv.from.to_notation(), mv.to.to_notation());
+        let piece = match self.get_piece(&mv.from) {
+            Some(piece) => *piece,
+            None => return coordinates(),
+        };
+        
+        let side = if piece.color == Color::White { "w" } else { "b" };
+        let is_en_passant = piece.piece_type == PieceType::Pawn
+            && mv.from.file != mv.to.file
+            && self.get_piece(&mv.to).is_none();
+        let en_passant = if is_en_passant { mv.to.to_notation() } else { String::from("-") };
+        
+        GameState::from_fen(&format!("{} {} KQkq {}", self.to_fen(), side, en_passant))
+            .and_then(|game| game.to_san(mv).ok())
+            .unwrap_or_else(coordinates)
+    }
+    
+    /// Finds the move of `side_to_move` written `san` in standard algebraic notation, e.g.
+    /// "Nbd7", "exd6", "e8=Q" or "O-O", ignoring "+" and "#" suffixes. This undoes
+    /// `move_to_san`.
+    ///
+    /// Only the board is consulted, so castling is allowed whenever the king and rook stand
+    /// on their squares, and a pawn may take en passant any enemy pawn beside it.
+    pub fn san_to_move(&self, san: &str, side_to_move: Color) -> Result<Move, SanError> {
+        let (piece_type, from_file, from_rank, to, promotion_piece) = match SanParts::parse(san)? {
+            SanParts::Castle { kingside } => {
+                let home_rank = if side_to_move == Color::White { 0 } else { 7 };
+                let from = Position::new_unchecked(4, home_rank);
+                let to = Position::new_unchecked(if kingside { 6 } else { 2 }, home_rank);
+                let is_own_king = self.get_piece(&from) == Some(&Piece::new(PieceType::King, side_to_move));
+                if !is_own_king || !self.can_castle(&from, &to) {
+                    return Err(SanError::NoMatchingMove);
+                }
+                return Ok(Move { from, to, promotion_piece: None });
+            }
+            SanParts::Move { piece_type, from_file, from_rank, to, promotion_piece } => {
+                (piece_type, from_file, from_rank, to, promotion_piece)
+            }
+        };
+        
+        let mover = Piece::new(piece_type, side_to_move);
+        let mut candidates: Vec<Position> = self.pieces.iter()
+            .filter(|&(&from, &piece)| {
+                piece == mover
+                    && from_file.is_none_or(|file| file == from.file)
+                    && from_rank.is_none_or(|rank| rank == from.rank)
+                    && (self.is_valid_move(&from, &to) || self.is_valid_en_passant(&from, &to))
+            })
+            .map(|(&from, _)| from)
+            .collect();
+        candidates.sort_by_key(|pos| (pos.rank, pos.file));
+        
+        SanParts::resolve(&candidates, to, promotion_piece)
+    }
+    
+    /// Checks if the pawn on `from` can take en passant on `to` without leaving its king in
+    /// check, supposing the enemy pawn beside it has just made its double step
+    fn is_valid_en_passant(&self, from: &Position, to: &Position) -> bool {
+        let color = match self.get_piece(from) {
+            Some(piece) => piece.color,
+            None => return false,
+        };
+        let mut after = self.clone();
+        after.capture_en_passant(from, to) && !after.is_king_in_check(color)
     }
     
     // Piece-specific move validation methods
@@ -229,7 +1129,7 @@ impl Board {
         // Pawns can move forward 2 squares from starting position
         let starting_rank = if color == Color::White { 1 } else { 6 };
         if file_diff == 0 && from.rank == starting_rank && rank_diff == 2 * direction {
-            let intermediate = Position::new(from.file, (from.rank as i8 + direction) as u8);
+            let intermediate = Position::new_unchecked(from.file, (from.rank as i8 + direction) as u8);
             return self.get_piece(&intermediate).is_none() && self.get_piece(to).is_none();
         }
         
@@ -279,39 +1179,37 @@ impl Board {
         let file_diff = (from.file as i8 - to.file as i8).abs();
         let rank_diff = (from.rank as i8 - to.rank as i8).abs();
         
-        // King can move one square in any direction
+        // King can move one square in any direction; castling needs the game's rights and
+        // goes through `can_castle`
         file_diff <= 1 && rank_diff <= 1
-        
-        // TODO: Implement castling
     }
     
-    // Check if path between positions is clear of assets
+    // Check if path between positions is clear of assets, in either direction along a rank,
+    // file or diagonal. Positions that aren't lined up have no path between them.
     fn is_path_clear(&self, from: &Position, to: &Position) -> bool {
-        let file_diff = to.file as i16 - from.file as i16;
-        let rank_diff = to.rank as i16 - from.rank as i16;
-        
-        let file_step = file_diff.signum();
-        let rank_step = rank_diff.signum();
-        
-        let mut file = from.file as i16 + file_step;
-        let mut rank = from.rank as i16 + rank_step;
-        
-        while file != to.file as i16 || rank != to.rank as i16 {
-            if self.get_piece(&Position::new(file as u8, rank as u8)).is_some() {
-                return false;
-            }
-            
-            file += file_step;
-            rank += rank_step;
+        let file_diff = to.file as i8 - from.file as i8;
+        let rank_diff = to.rank as i8 - from.rank as i8;
+        if file_diff != 0 && rank_diff != 0 && file_diff.abs() != rank_diff.abs() {
+            return false;
         }
         
-        true
+        from.ray(file_diff.signum(), rank_diff.signum())
+            .take_while(|pos| pos != to)
+            .all(|pos| self.get_piece(&pos).is_none())
+    }
+}
+
+/// Draws the board from White's side as `to_ascii` does
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_ascii(Color::White))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::state::GameStatus;
     use crate::types::{Color, Piece, PieceType, Position};
     
     #[test]
@@ -323,7 +1221,7 @@ mod tests {
     #[test]
     fn test_set_and_get_piece() {
         let mut board = Board::new();
-        let pos = Position::new(3, 4);
+        let pos = Position::new_unchecked(3, 4);
         let piece = Piece::new(PieceType::Queen, Color::White);
         
         board.set_piece(pos, piece.clone());
@@ -335,7 +1233,7 @@ mod tests {
     #[test]
     fn test_remove_piece() {
         let mut board = Board::new();
-        let pos = Position::new(1, 1);
+        let pos = Position::new_unchecked(1, 1);
         let piece = Piece::new(PieceType::Pawn, Color::Black);
         
         board.set_piece(pos, piece.clone());
@@ -349,7 +1247,7 @@ mod tests {
     #[test]
     fn test_new_game_has_32_pieces() {
         let board = Board::new_game();
-        assert_eq!(board.pieces.len(), 32);
+        assert_eq!(board.piece_count(), 32);
     }
     
     #[test]
@@ -358,7 +1256,7 @@ mod tests {
         
         // Check white pawns
         for file in 0..8 {
-            let pos = Position::new(file, 1);
+            let pos = Position::new_unchecked(file, 1);
             let piece = board.get_piece(&pos).unwrap();
             assert_eq!(piece.piece_type, PieceType::Pawn);
             assert_eq!(piece.color, Color::White);
@@ -366,7 +1264,7 @@ mod tests {
         
         // Check black pawns
         for file in 0..8 {
-            let pos = Position::new(file, 6);
+            let pos = Position::new_unchecked(file, 6);
             let piece = board.get_piece(&pos).unwrap();
             assert_eq!(piece.piece_type, PieceType::Pawn);
             assert_eq!(piece.color, Color::Black);
@@ -378,33 +1276,33 @@ mod tests {
         let board = Board::new_game();
         
         // Test piece layout for white assets
-        assert_eq!(board.get_piece(&Position::new(0, 0)).unwrap().piece_type, PieceType::Rook);
-        assert_eq!(board.get_piece(&Position::new(1, 0)).unwrap().piece_type, PieceType::Knight);
-        assert_eq!(board.get_piece(&Position::new(2, 0)).unwrap().piece_type, PieceType::Bishop);
-        assert_eq!(board.get_piece(&Position::new(3, 0)).unwrap().piece_type, PieceType::Queen);
-        assert_eq!(board.get_piece(&Position::new(4, 0)).unwrap().piece_type, PieceType::King);
-        assert_eq!(board.get_piece(&Position::new(5, 0)).unwrap().piece_type, PieceType::Bishop);
-        assert_eq!(board.get_piece(&Position::new(6, 0)).unwrap().piece_type, PieceType::Knight);
-        assert_eq!(board.get_piece(&Position::new(7, 0)).unwrap().piece_type, PieceType::Rook);
+        assert_eq!(board.get_piece(&Position::new_unchecked(0, 0)).unwrap().piece_type, PieceType::Rook);
+        assert_eq!(board.get_piece(&Position::new_unchecked(1, 0)).unwrap().piece_type, PieceType::Knight);
+        assert_eq!(board.get_piece(&Position::new_unchecked(2, 0)).unwrap().piece_type, PieceType::Bishop);
+        assert_eq!(board.get_piece(&Position::new_unchecked(3, 0)).unwrap().piece_type, PieceType::Queen);
+        assert_eq!(board.get_piece(&Position::new_unchecked(4, 0)).unwrap().piece_type, PieceType::King);
+        assert_eq!(board.get_piece(&Position::new_unchecked(5, 0)).unwrap().piece_type, PieceType::Bishop);
+        assert_eq!(board.get_piece(&Position::new_unchecked(6, 0)).unwrap().piece_type, PieceType::Knight);
+        assert_eq!(board.get_piece(&Position::new_unchecked(7, 0)).unwrap().piece_type, PieceType::Rook);
         
         // Test colors for white assets
         for file in 0..8 {
-            assert_eq!(board.get_piece(&Position::new(file, 0)).unwrap().color, Color::White);
+            assert_eq!(board.get_piece(&Position::new_unchecked(file, 0)).unwrap().color, Color::White);
         }
         
         // Test piece layout for black assets
-        assert_eq!(board.get_piece(&Position::new(0, 7)).unwrap().piece_type, PieceType::Rook);
-        assert_eq!(board.get_piece(&Position::new(1, 7)).unwrap().piece_type, PieceType::Knight);
-        assert_eq!(board.get_piece(&Position::new(2, 7)).unwrap().piece_type, PieceType::Bishop);
-        assert_eq!(board.get_piece(&Position::new(3, 7)).unwrap().piece_type, PieceType::Queen);
-        assert_eq!(board.get_piece(&Position::new(4, 7)).unwrap().piece_type, PieceType::King);
-        assert_eq!(board.get_piece(&Position::new(5, 7)).unwrap().piece_type, PieceType::Bishop);
-        assert_eq!(board.get_piece(&Position::new(6, 7)).unwrap().piece_type, PieceType::Knight);
-        assert_eq!(board.get_piece(&Position::new(7, 7)).unwrap().piece_type, PieceType::Rook);
+        assert_eq!(board.get_piece(&Position::new_unchecked(0, 7)).unwrap().piece_type, PieceType::Rook);
+        assert_eq!(board.get_piece(&Position::new_unchecked(1, 7)).unwrap().piece_type, PieceType::Knight);
+        assert_eq!(board.get_piece(&Position::new_unchecked(2, 7)).unwrap().piece_type, PieceType::Bishop);
+        assert_eq!(board.get_piece(&Position::new_unchecked(3, 7)).unwrap().piece_type, PieceType::Queen);
+        assert_eq!(board.get_piece(&Position::new_unchecked(4, 7)).unwrap().piece_type, PieceType::King);
+        assert_eq!(board.get_piece(&Position::new_unchecked(5, 7)).unwrap().piece_type, PieceType::Bishop);
+        assert_eq!(board.get_piece(&Position::new_unchecked(6, 7)).unwrap().piece_type, PieceType::Knight);
+        assert_eq!(board.get_piece(&Position::new_unchecked(7, 7)).unwrap().piece_type, PieceType::Rook);
         
         // Test colors for black assets
         for file in 0..8 {
-            assert_eq!(board.get_piece(&Position::new(file, 7)).unwrap().color, Color::Black);
+            assert_eq!(board.get_piece(&Position::new_unchecked(file, 7)).unwrap().color, Color::Black);
         }
     }
     
@@ -413,9 +1311,9 @@ mod tests {
         let mut board = Board::new_game();
         
         // Test pawn moves
-        let e2 = Position::new(4, 1);
-        let e3 = Position::new(4, 2);
-        let e4 = Position::new(4, 3);
+        let e2 = Position::new_unchecked(4, 1);
+        let e3 = Position::new_unchecked(4, 2);
+        let e4 = Position::new_unchecked(4, 3);
         
         // Valid single pawn move
         assert!(board.is_valid_move(&e2, &e3));
@@ -424,14 +1322,14 @@ mod tests {
         assert!(board.is_valid_move(&e2, &e4));
         
         // Invalid backward pawn move
-        let backward = Position::new(4, 0);
+        let backward = Position::new_unchecked(4, 0);
         assert!(!board.is_valid_move(&e2, &backward));
         
         // Test knight moves
-        let g1 = Position::new(6, 0);  // White knight starting position
-        let f3 = Position::new(5, 2);
-        let h3 = Position::new(7, 2);
-        let e2 = Position::new(4, 1);
+        let g1 = Position::new_unchecked(6, 0);  // White knight starting position
+        let f3 = Position::new_unchecked(5, 2);
+        let h3 = Position::new_unchecked(7, 2);
+        let e2 = Position::new_unchecked(4, 1);
         
         // Valid knight moves
         assert!(board.is_valid_move(&g1, &f3));
@@ -441,66 +1339,645 @@ mod tests {
         assert!(!board.is_valid_move(&g1, &e2));
         
         // Test bishop move (need to clear path first)
-        board.remove_piece(&Position::new(4, 1)); // Remove pawn blocking bishop
-        let f1 = Position::new(5, 0);  // White bishop starting position
-        let b5 = Position::new(1, 4);
+        board.remove_piece(&Position::new_unchecked(4, 1)); // Remove pawn blocking bishop
+        let f1 = Position::new_unchecked(5, 0);  // White bishop starting position
+        let b5 = Position::new_unchecked(1, 4);
         
         // Valid bishop move
         assert!(board.is_valid_move(&f1, &b5));
         
         // Test illegal move (blocked path)
-        let blocked_pos = Position::new(3, 2);
+        let blocked_pos = Position::new_unchecked(3, 2);
         board.set_piece(blocked_pos, Piece::new(PieceType::Pawn, Color::White));
         assert!(!board.is_valid_move(&f1, &b5));
     }
     
+    #[test]
+    #[cfg(debug_assertions)]
+    fn test_move_invariants_hold() {
+        // A quiet move and a capture both run the checks inside make_move.
+        // Castling and en passant aren't generated yet.
+        let mut board = Board::new_game();
+        assert!(board.make_move(&Position::new_unchecked(4, 1), &Position::new_unchecked(4, 3)));
+        assert!(board.make_move(&Position::new_unchecked(3, 6), &Position::new_unchecked(3, 4)));
+        assert!(board.make_move(&Position::new_unchecked(4, 3), &Position::new_unchecked(3, 4)));
+        assert_eq!(board.piece_count(), 31);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "piece count changed")]
+    fn test_move_invariants_catch_corruption() {
+        let before = Board::new_game();
+        let (from, to) = (Position::new_unchecked(6, 0), Position::new_unchecked(5, 2));
+
+        // A knight move that also wipes out two pawns
+        let mut after = before.clone();
+        after.make_move_unchecked(&from, &to);
+        after.remove_piece(&Position::new_unchecked(0, 6));
+        after.remove_piece(&Position::new_unchecked(1, 6));
+
+        Board::assert_move_invariants(&before, &after, &from, &to);
+    }
+
+    fn board_with(pieces: &[(&str, PieceType, Color)]) -> Board {
+        let mut board = Board::new();
+        for &(square, piece_type, color) in pieces {
+            board.set_piece(Position::from_notation(square).unwrap(), Piece::new(piece_type, color));
+        }
+        board
+    }
+
+    #[test]
+    fn test_attack_weight_near_king() {
+        let quiet = board_with(&[
+            ("g1", PieceType::King, Color::White),
+            ("f2", PieceType::Pawn, Color::White),
+            ("g2", PieceType::Pawn, Color::White),
+            ("h2", PieceType::Pawn, Color::White),
+            ("e8", PieceType::King, Color::Black),
+            ("a8", PieceType::Rook, Color::Black),
+            ("d8", PieceType::Queen, Color::Black),
+        ]);
+        assert_eq!(quiet.attack_weight_near_king(Color::White), 0);
+
+        // The queen on h4 hits h2 and the rook on the open f-file hits f2
+        let exposed = board_with(&[
+            ("g1", PieceType::King, Color::White),
+            ("f2", PieceType::Pawn, Color::White),
+            ("g2", PieceType::Pawn, Color::White),
+            ("h2", PieceType::Pawn, Color::White),
+            ("e8", PieceType::King, Color::Black),
+            ("f8", PieceType::Rook, Color::Black),
+            ("h4", PieceType::Queen, Color::Black),
+        ]);
+        assert_eq!(exposed.attack_weight_near_king(Color::White), 8);
+        assert!(exposed.attack_weight_near_king(Color::White) > quiet.attack_weight_near_king(Color::White));
+    }
+
+    #[test]
+    fn test_discovered_attack_moves() {
+        // The knight on d2 stands between the bishop on c1 and the queen on g5
+        let board = board_with(&[
+            ("e1", PieceType::King, Color::White),
+            ("e8", PieceType::King, Color::Black),
+            ("c1", PieceType::Bishop, Color::White),
+            ("d2", PieceType::Knight, Color::White),
+            ("g5", PieceType::Queen, Color::Black),
+        ]);
+        let moves = board.discovered_attack_moves(Color::White);
+        let d2 = Position::from_notation("d2").unwrap();
+
+        assert!(moves.contains(&(d2, Position::from_notation("f3").unwrap())));
+        assert!(moves.iter().all(|&(from, _)| from == d2));
+        assert_eq!(moves.len(), board.get_valid_moves(&d2).len());
+
+        // Nothing is uncovered once the queen leaves the diagonal
+        let mut board = board;
+        board.remove_piece(&Position::from_notation("g5").unwrap());
+        board.set_piece(Position::from_notation("g6").unwrap(), Piece::new(PieceType::Queen, Color::Black));
+        assert!(board.discovered_attack_moves(Color::White).is_empty());
+    }
+
+    #[test]
+    fn test_king_cache_follows_king_moves() {
+        let mut board = board_with(&[
+            ("e1", PieceType::King, Color::White),
+            ("h8", PieceType::King, Color::Black),
+            ("e7", PieceType::Rook, Color::Black),
+        ]);
+        let square = |name: &str| Position::from_notation(name).unwrap();
+        assert_eq!(board.find_king(Color::White), Some(square("e1")));
+        assert!(board.is_king_in_check(Color::White));
+        
+        assert!(board.make_move(&square("e1"), &square("d1")));
+        assert_eq!(board.find_king(Color::White), Some(square("d1")));
+        assert!(!board.is_king_in_check(Color::White));
+        
+        assert!(board.make_move(&square("d1"), &square("d2")));
+        assert!(board.make_move(&square("e7"), &square("d7")));
+        assert!(board.is_king_in_check(Color::White));
+        
+        // Taking the king off the board, or covering its square, clears the cache
+        board.set_piece(square("d2"), Piece::new(PieceType::Queen, Color::White));
+        assert_eq!(board.find_king(Color::White), None);
+        assert!(!board.is_king_in_check(Color::White));
+        board.remove_piece(&square("h8"));
+        assert_eq!(board.find_king(Color::Black), None);
+    }
+
+    #[test]
+    fn test_iter_squares_and_pieces() {
+        let squares: Vec<Position> = Board::iter_squares().collect();
+        assert_eq!(squares.len(), 64);
+        assert_eq!(squares[0], Position::new_unchecked(0, 0));
+        assert_eq!(squares[1], Position::new_unchecked(1, 0));
+        assert_eq!(squares[63], Position::new_unchecked(7, 7));
+        
+        // White's back rank comes first, Black's last
+        let board = Board::new_game();
+        let pieces: Vec<(Position, &Piece)> = board.iter_pieces().collect();
+        assert_eq!(pieces.len(), 32);
+        assert_eq!(pieces[0], (Position::new_unchecked(0, 0), &Piece::new(PieceType::Rook, Color::White)));
+        assert_eq!(pieces[31], (Position::new_unchecked(7, 7), &Piece::new(PieceType::Rook, Color::Black)));
+        assert!(pieces.windows(2).all(|pair| (pair[0].0.rank, pair[0].0.file) < (pair[1].0.rank, pair[1].0.file)));
+        let white = board.iter_pieces().filter(|(_, piece)| piece.color == Color::White).count();
+        assert_eq!(white, board.count_pieces_by_color(Color::White));
+    }
+    
+    #[test]
+    fn test_king_position() {
+        let mut board = Board::new_game();
+        let square = |name: &str| Position::from_notation(name).unwrap();
+        assert_eq!(board.king_position(Color::White), Some(square("e1")));
+        assert_eq!(board.king_position(Color::Black), Some(square("e8")));
+        
+        // Other pieces moving leave the king where it was
+        assert!(board.make_move(&square("e2"), &square("e4")));
+        assert!(board.make_move(&square("g1"), &square("f3")));
+        assert_eq!(board.king_position(Color::White), Some(square("e1")));
+        
+        assert!(board.make_move(&square("e1"), &square("e2")));
+        assert_eq!(board.king_position(Color::White), Some(square("e2")));
+        assert_eq!(board.king_position(Color::Black), Some(square("e8")));
+        
+        board.remove_piece(&square("e8"));
+        assert_eq!(board.king_position(Color::Black), None);
+    }
+
+    #[test]
+    fn test_check_types() {
+        let e8 = Position::from_notation("e8").unwrap();
+
+        // The rook on e1 checks after the knight leaves the e-file
+        let discovered = board_with(&[
+            ("e8", PieceType::King, Color::Black),
+            ("a1", PieceType::King, Color::White),
+            ("e1", PieceType::Rook, Color::White),
+            ("c3", PieceType::Knight, Color::White),
+        ]);
+        let c3 = Position::from_notation("c3").unwrap();
+        assert_eq!(discovered.checkers(Color::Black), vec![Position::from_notation("e1").unwrap()]);
+        assert_eq!(discovered.check_type(Color::Black, &c3), Some(CheckType::Discovered));
+
+        // The knight on d6 checks directly
+        let mut direct = discovered.clone();
+        direct.remove_piece(&c3);
+        direct.set_piece(Position::from_notation("e5").unwrap(), Piece::new(PieceType::Pawn, Color::White));
+        let d6 = Position::from_notation("d6").unwrap();
+        direct.set_piece(d6, Piece::new(PieceType::Knight, Color::White));
+        assert_eq!(direct.check_type(Color::Black, &d6), Some(CheckType::Direct));
+
+        // The knight on d6 checks and uncovers the rook
+        let mut double = discovered.clone();
+        double.remove_piece(&c3);
+        double.set_piece(d6, Piece::new(PieceType::Knight, Color::White));
+        assert_eq!(double.checkers(Color::Black).len(), 2);
+        assert_eq!(double.check_type(Color::Black, &d6), Some(CheckType::Double));
+
+        assert_eq!(board_with(&[("e8", PieceType::King, Color::Black)]).check_type(Color::Black, &e8), None);
+    }
+
+    #[test]
+    fn test_is_valid_move_for_enforces_turn() {
+        let board = Board::new_game();
+        let e7 = Position::new_unchecked(4, 6);
+        let e5 = Position::new_unchecked(4, 4);
+        
+        // Black's pawn move is geometrically fine but it's White's turn
+        assert!(board.is_valid_move(&e7, &e5));
+        assert!(!board.is_valid_move_for(&e7, &e5, Color::White));
+        assert!(board.is_valid_move_for(&e7, &e5, Color::Black));
+        
+        let e2 = Position::new_unchecked(4, 1);
+        let e4 = Position::new_unchecked(4, 3);
+        assert!(board.is_valid_move_for(&e2, &e4, Color::White));
+        assert!(!board.is_valid_move_for(&e2, &e4, Color::Black));
+    }
+    
+    #[test]
+    fn test_king_cannot_approach_enemy_king() {
+        let mut board = Board::new();
+        let e4 = Position::new_unchecked(4, 3);
+        board.set_piece(e4, Piece::new(PieceType::King, Color::White));
+        board.set_piece(Position::new_unchecked(4, 5), Piece::new(PieceType::King, Color::Black));
+        
+        // d5, e5 and f5 all touch the black king on e6
+        for file in 3..=5 {
+            assert!(!board.is_valid_move(&e4, &Position::new_unchecked(file, 4)));
+        }
+        
+        let moves = board.get_valid_moves(&e4);
+        assert_eq!(moves.len(), 5);
+        assert!(moves.iter().all(|pos| pos.rank <= 3));
+    }
+    
     #[test]
     fn test_diagonal_and_straight_moves() {
         let board = Board::new();
         
         // Test diagonal moves
-        let a1 = Position::new(0, 0);
-        let h8 = Position::new(7, 7);
+        let a1 = Position::new_unchecked(0, 0);
+        let h8 = Position::new_unchecked(7, 7);
         assert!(board.is_diagonal_move(&a1, &h8));
         
-        let e4 = Position::new(4, 3);
-        let b7 = Position::new(1, 6);
+        let e4 = Position::new_unchecked(4, 3);
+        let b7 = Position::new_unchecked(1, 6);
         assert!(board.is_diagonal_move(&e4, &b7));
         
         // Non-diagonal move
-        let a2 = Position::new(0, 1);
+        let a2 = Position::new_unchecked(0, 1);
         assert!(!board.is_diagonal_move(&a1, &a2));
         
         // Test straight moves
-        let a1 = Position::new(0, 0);
-        let a8 = Position::new(0, 7);
+        let a1 = Position::new_unchecked(0, 0);
+        let a8 = Position::new_unchecked(0, 7);
         assert!(board.is_straight_move(&a1, &a8));
         
-        let e4 = Position::new(4, 3);
-        let h4 = Position::new(7, 3);
+        let e4 = Position::new_unchecked(4, 3);
+        let h4 = Position::new_unchecked(7, 3);
         assert!(board.is_straight_move(&e4, &h4));
         
         // Neither straight nor diagonal
-        let b3 = Position::new(1, 2);
+        let b3 = Position::new_unchecked(1, 2);
         assert!(!board.is_straight_move(&a1, &b3));
         assert!(!board.is_diagonal_move(&a1, &h4));
     }
-    
+
+    #[test]
+    fn test_path_clear_in_every_direction() {
+        let d4 = Position::from_notation("d4").unwrap();
+
+        // Diagonals towards each corner, then the rank and file both ways
+        for (end, blocker) in [
+            ("h8", "f6"), ("a1", "b2"), ("a7", "c5"), ("g1", "f2"),
+            ("d8", "d6"), ("d1", "d2"), ("a4", "b4"), ("h4", "g4"),
+        ] {
+            let end = Position::from_notation(end).unwrap();
+            let blocker = Position::from_notation(blocker).unwrap();
+
+            let mut board = Board::new();
+            assert!(board.is_path_clear(&d4, &end));
+            assert!(board.is_path_clear(&end, &d4));
+
+            board.set_piece(blocker, Piece::new(PieceType::Pawn, Color::White));
+            assert!(!board.is_path_clear(&d4, &end));
+            assert!(!board.is_path_clear(&end, &d4));
+        }
+
+        // Squares that don't share a line have no path
+        let board = Board::new();
+        assert!(!board.is_path_clear(&d4, &Position::from_notation("e6").unwrap()));
+        assert!(!board.is_path_clear(&Position::from_notation("h1").unwrap(), &Position::from_notation("a2").unwrap()));
+    }
+
     #[test]
     fn test_make_move() {
         let mut board = Board::new_game();
         
         // Test valid pawn move
-        let e2 = Position::new(4, 1);
-        let e4 = Position::new(4, 3);
+        let e2 = Position::new_unchecked(4, 1);
+        let e4 = Position::new_unchecked(4, 3);
         assert!(board.make_move(&e2, &e4));
         assert!(board.get_piece(&e2).is_none());
         assert!(board.get_piece(&e4).is_some());
         
         // Test invalid move
-        let a7 = Position::new(0, 6);
-        let a6 = Position::new(0, 5);
+        let a7 = Position::new_unchecked(0, 6);
+        let a6 = Position::new_unchecked(0, 5);
         assert!(!board.make_move(&a7, &a6));
     }
+    
+    #[test]
+    fn test_to_ascii_orientation() {
+        let board = Board::new_game();
+        
+        let white = board.to_ascii(Color::White);
+        let lines: Vec<&str> = white.lines().collect();
+        assert_eq!(lines[0], "8 r n b q k b n r");
+        assert_eq!(lines[7], "1 R N B Q K B N R");
+        assert_eq!(lines[8], "  a b c d e f g h");
+        
+        let black = board.to_ascii(Color::Black);
+        let lines: Vec<&str> = black.lines().collect();
+        assert_eq!(lines[0], "1 R N B K Q B N R");
+        assert_eq!(lines[4], "5 . . . . . . . .");
+        assert_eq!(lines[7], "8 r n b k q b n r");
+        assert_eq!(lines[8], "  h g f e d c b a");
+        assert!(lines[8].ends_with('a'));
+    }
+    
+    #[test]
+    fn test_display_and_unicode_string() {
+        let board = Board::new_game();
+        assert_eq!(board.to_string(), board.to_ascii(Color::White));
+        
+        let glyphs = board.to_unicode_string(Color::White);
+        let lines: Vec<&str> = glyphs.lines().collect();
+        assert_eq!(lines[0], "8 ♜ ♞ ♝ ♛ ♚ ♝ ♞ ♜");
+        assert_eq!(lines[4], "4 . . . . . . . .");
+        assert_eq!(lines[8], "  a b c d e f g h");
+        assert!(!glyphs.contains('\x1b'));
+    }
+    
+    #[test]
+    fn test_to_unicode_shows_glyphs() {
+        let board = Board::new_game();
+        
+        let white = board.to_unicode(Color::White);
+        let lines: Vec<&str> = white.lines().collect();
+        assert!(lines[7].starts_with("1 "));
+        
+        // Each square ends with a reset code, so the fifth square of rank 1 is e1
+        let squares: Vec<&str> = lines[7].split("\x1b[0m").collect();
+        assert!(squares[4].ends_with(" ♔ "));
+        assert!(squares[0].ends_with(" ♖ "));
+        assert_eq!(lines[8], "   a  b  c  d  e  f  g  h");
+        
+        let black = board.to_unicode(Color::Black);
+        let squares: Vec<&str> = black.lines().next().unwrap().split("\x1b[0m").collect();
+        assert!(squares[3].ends_with(" ♔ "));
+    }
+
+    #[test]
+    fn test_promote_pawn() {
+        let mut board = Board::new();
+        board.set_piece(Position::new_unchecked(4, 0), Piece::new(PieceType::King, Color::White));
+        board.set_piece(Position::new_unchecked(7, 7), Piece::new(PieceType::King, Color::Black));
+        board.set_piece(Position::new_unchecked(0, 6), Piece::new(PieceType::Pawn, Color::White));
+        
+        let a8 = Position::new_unchecked(0, 7);
+        assert!(board.make_move(&Position::new_unchecked(0, 6), &a8));
+        assert_eq!(board.promote_pawn(a8, PieceType::Queen), Ok(()));
+        assert_eq!(board.get_piece(&a8), Some(&Piece::new(PieceType::Queen, Color::White)));
+        
+        // No pawn to promote
+        assert_eq!(board.promote_pawn(a8, PieceType::Rook), Err(PromotionError::NoPawn));
+        assert_eq!(board.promote_pawn(Position::new_unchecked(3, 3), PieceType::Rook), Err(PromotionError::NoPawn));
+        
+        // Pawns that haven't arrived yet, or invalid choices
+        board.set_piece(Position::new_unchecked(1, 5), Piece::new(PieceType::Pawn, Color::White));
+        assert_eq!(board.promote_pawn(Position::new_unchecked(1, 5), PieceType::Queen), Err(PromotionError::NotOnLastRank));
+        board.set_piece(Position::new_unchecked(2, 0), Piece::new(PieceType::Pawn, Color::Black));
+        assert_eq!(board.promote_pawn(Position::new_unchecked(2, 0), PieceType::King), Err(PromotionError::InvalidPiece(PieceType::King)));
+    }
+
+    #[test]
+    fn test_zobrist_hash_is_stable() {
+        // Pinned so a change to the keys can't go unnoticed; see `zobrist::SEED`
+        assert_eq!(Board::new_game().zobrist_hash(Color::White), 0x1759_ec97_60ed_52c3);
+        assert_eq!(Board::new_game().piece_hash(), 0x1759_ec97_60ed_52c3);
+    }
+
+    #[test]
+    fn test_fen_placement_roundtrip() {
+        let board = Board::new_game();
+        assert_eq!(board.to_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR");
+        assert_eq!(Board::from_fen(&board.to_fen()), Ok(board));
+        
+        // Trailing fields are ignored
+        let after_e4 = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+        assert_eq!(after_e4.get_piece(&Position::new_unchecked(4, 3)), Some(&Piece::new(PieceType::Pawn, Color::White)));
+        
+        // A middlegame with gaps of every length
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R";
+        let board = Board::from_fen(kiwipete).unwrap();
+        assert_eq!(board.to_fen(), kiwipete);
+        assert_eq!(Board::from_fen(&board.to_fen()), Ok(board));
+        
+        assert_eq!(Board::from_fen(""), Err(FenError::WrongRankCount(1)));
+        assert_eq!(Board::from_fen("8/8/8"), Err(FenError::WrongRankCount(3)));
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/8/8"), Err(FenError::WrongRankCount(9)));
+        assert_eq!(Board::from_fen("9/8/8/8/8/8/8/8"), Err(FenError::BadFileSum { rank: 7 }));
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/ppppppp"), Err(FenError::BadFileSum { rank: 0 }));
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/99999999"), Err(FenError::BadFileSum { rank: 0 }));
+        assert_eq!(Board::from_fen("8/8/8/8/8/8/8/ppppppppp"), Err(FenError::BadFileSum { rank: 0 }));
+        assert_eq!(Board::from_fen("x7/8/8/8/8/8/8/8"), Err(FenError::UnknownPiece('x')));
+    }
+    
+    #[test]
+    fn test_try_from_placements() {
+        let white_king = Piece::new(PieceType::King, Color::White);
+        let black_king = Piece::new(PieceType::King, Color::Black);
+        let white_queen = Piece::new(PieceType::Queen, Color::White);
+        
+        // King and queen against king
+        let board = Board::try_from_placements(&[
+            (Position::new_unchecked(4, 0), white_king),
+            (Position::new_unchecked(3, 0), white_queen),
+            (Position::new_unchecked(4, 7), black_king),
+        ]).unwrap();
+        assert_eq!(board.get_piece(&Position::new_unchecked(3, 0)), Some(&white_queen));
+        
+        // Two white kings
+        let result = Board::try_from_placements(&[
+            (Position::new_unchecked(4, 0), white_king),
+            (Position::new_unchecked(0, 0), white_king),
+            (Position::new_unchecked(4, 7), black_king),
+        ]);
+        assert_eq!(result, Err(LayoutError::KingCount { color: Color::White, count: 2 }));
+    }
+
+    #[test]
+    fn test_from_grid_back_rank_mate() {
+        let board = Board::from_grid([
+            ['R', '.', '.', '.', '.', '.', 'k', '.'],
+            ['.', '.', '.', '.', '.', 'p', 'p', 'p'],
+            ['.', '.', '.', '.', '.', '.', '.', '.'],
+            ['.', '.', '.', '.', '.', '.', '.', '.'],
+            ['.', '.', '.', '.', '.', '.', '.', '.'],
+            ['.', '.', '.', '.', '.', '.', '.', '.'],
+            ['.', '.', '.', '.', '.', '.', '.', '.'],
+            ['.', '.', '.', '.', '.', '.', 'K', '.'],
+        ]).unwrap();
+        assert_eq!(board.to_fen(), "R5k1/5ppp/8/8/8/8/8/6K1");
+
+        let mut game_state = GameState::new();
+        game_state.board = board;
+        game_state.current_player = Color::Black;
+        game_state.refresh_status();
+        assert_eq!(game_state.status, GameStatus::Checkmate { winner: Color::White });
+
+        let mut rows = [['.'; 8]; 8];
+        rows[0][4] = 'x';
+        assert_eq!(Board::from_grid(rows), Err(LayoutError::UnknownPiece('x')));
+    }
+    
+    #[test]
+    fn test_insufficient_material() {
+        let mut board = Board::new();
+        board.set_piece(Position::new_unchecked(4, 0), Piece::new(PieceType::King, Color::White));
+        board.set_piece(Position::new_unchecked(4, 7), Piece::new(PieceType::King, Color::Black));
+        assert!(board.is_insufficient_material());
+        
+        // Bishops on c1 and f8 both stand on dark squares
+        board.set_piece(Position::new_unchecked(2, 0), Piece::new(PieceType::Bishop, Color::White));
+        board.set_piece(Position::new_unchecked(5, 7), Piece::new(PieceType::Bishop, Color::Black));
+        assert!(board.is_insufficient_material());
+        
+        // Moving Black's bishop to a light square makes mate possible again
+        board.remove_piece(&Position::new_unchecked(5, 7));
+        board.set_piece(Position::new_unchecked(2, 7), Piece::new(PieceType::Bishop, Color::Black));
+        assert!(!board.is_insufficient_material());
+        
+        assert!(!Board::new_game().is_insufficient_material());
+    }
+    
+    #[test]
+    fn test_locked_pawn_chain_is_dead_position() {
+        let mut board = Board::new();
+        board.set_piece(Position::new_unchecked(4, 0), Piece::new(PieceType::King, Color::White));
+        board.set_piece(Position::new_unchecked(4, 7), Piece::new(PieceType::King, Color::Black));
+        
+        // A zig-zag wall across the board: white pawns on a3, b4, c3, ... blocked by black pawns
+        for file in 0..8 {
+            let white_rank = if file % 2 == 0 { 2 } else { 3 };
+            board.set_piece(Position::new_unchecked(file, white_rank), Piece::new(PieceType::Pawn, Color::White));
+            board.set_piece(Position::new_unchecked(file, white_rank + 1), Piece::new(PieceType::Pawn, Color::Black));
+        }
+        assert!(!board.is_insufficient_material());
+        assert!(board.is_dead_position());
+        
+        // Without the pawn on h5 the h4 pawn can advance
+        board.remove_piece(&Position::new_unchecked(7, 4));
+        assert!(!board.is_dead_position());
+    }
+    
+    /// Legal moves for a piece computed with the clone-and-check path for every destination
+    fn naive_valid_moves(board: &Board, pos: &Position) -> Vec<Position> {
+        let mut moves = Vec::new();
+        for rank in 0..8 {
+            for file in 0..8 {
+                let dest = Position::new_unchecked(file, rank);
+                if board.is_valid_move(pos, &dest) {
+                    moves.push(dest);
+                }
+            }
+        }
+        moves
+    }
+    
+    fn assert_fast_path_matches_naive(board: &Board) {
+        for rank in 0..8 {
+            for file in 0..8 {
+                let pos = Position::new_unchecked(file, rank);
+                assert_eq!(
+                    board.get_valid_moves(&pos),
+                    naive_valid_moves(board, &pos),
+                    "move lists differ for piece on {}",
+                    pos.to_notation()
+                );
+            }
+        }
+    }
+    
+    #[test]
+    fn test_valid_moves_fast_path_matches_naive() {
+        // Opening position
+        assert_fast_path_matches_naive(&Board::new_game());
+        
+        // Knight on e2 pinned by a rook on e8, bishop on d2 pinned by a bishop on a5
+        let mut pinned = Board::new();
+        pinned.set_piece(Position::new_unchecked(4, 0), Piece::new(PieceType::King, Color::White));
+        pinned.set_piece(Position::new_unchecked(4, 1), Piece::new(PieceType::Knight, Color::White));
+        pinned.set_piece(Position::new_unchecked(3, 1), Piece::new(PieceType::Bishop, Color::White));
+        pinned.set_piece(Position::new_unchecked(0, 1), Piece::new(PieceType::Rook, Color::White));
+        pinned.set_piece(Position::new_unchecked(4, 7), Piece::new(PieceType::Rook, Color::Black));
+        pinned.set_piece(Position::new_unchecked(0, 4), Piece::new(PieceType::Bishop, Color::Black));
+        pinned.set_piece(Position::new_unchecked(7, 7), Piece::new(PieceType::King, Color::Black));
+        assert_eq!(pinned.pinned_pieces(Color::White).len(), 2);
+        assert_fast_path_matches_naive(&pinned);
+        
+        // White in check from a queen, so every move must address the check
+        let mut check = Board::new();
+        check.set_piece(Position::new_unchecked(4, 0), Piece::new(PieceType::King, Color::White));
+        check.set_piece(Position::new_unchecked(4, 6), Piece::new(PieceType::Queen, Color::Black));
+        check.set_piece(Position::new_unchecked(1, 2), Piece::new(PieceType::Knight, Color::White));
+        check.set_piece(Position::new_unchecked(0, 3), Piece::new(PieceType::Rook, Color::White));
+        check.set_piece(Position::new_unchecked(0, 7), Piece::new(PieceType::King, Color::Black));
+        assert_fast_path_matches_naive(&check);
+        
+        // A scattered middlegame with two friendly pieces shielding the king on one ray
+        let mut middlegame = Board::new_game();
+        middlegame.make_move_unchecked(&Position::new_unchecked(4, 1), &Position::new_unchecked(4, 3));
+        middlegame.make_move_unchecked(&Position::new_unchecked(3, 6), &Position::new_unchecked(3, 4));
+        middlegame.make_move_unchecked(&Position::new_unchecked(5, 0), &Position::new_unchecked(1, 4));
+        middlegame.make_move_unchecked(&Position::new_unchecked(3, 7), &Position::new_unchecked(0, 4));
+        assert_fast_path_matches_naive(&middlegame);
+    }
+    
+    #[test]
+    fn test_move_to_san() {
+        let mv = |text: &str| Move {
+            from: Position::from_notation(&text[..2]).unwrap(),
+            to: Position::from_notation(&text[2..4]).unwrap(),
+            promotion_piece: None,
+        };
+        let san = |fen: &str, text: &str| Board::from_fen(fen).unwrap().move_to_san(&mv(text));
+        
+        // Two knights reaching the same square are told apart by file, or by rank on one file
+        assert_eq!(san("4k3/8/8/8/8/5N2/8/1N2K3", "b1d2"), "Nbd2");
+        assert_eq!(san("4k3/8/8/8/8/5N2/8/1N2K3", "f3d2"), "Nfd2");
+        assert_eq!(san("4k3/8/8/6N1/8/8/8/4K1N1", "g1f3"), "N1f3");
+        assert_eq!(san("4k3/8/8/6N1/8/8/8/4K1N1", "g5f3"), "N5f3");
+        // Only one knight can reach e5, and a pinned knight doesn't count as a rival
+        assert_eq!(san("4k3/8/8/8/8/5N2/8/1N2K3", "f3e5"), "Ne5");
+        assert_eq!(san("4r1k1/8/8/8/8/8/4N3/1N2K3", "b1c3"), "Nc3");
+        // A queen sharing a file with one rival and a rank with another needs both
+        assert_eq!(san("4k3/8/8/8/8/Q7/8/Q1Q1K3", "a1b2"), "Qa1b2");
+        
+        assert_eq!(san("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR", "e4d5"), "exd5");
+        assert_eq!(san("4k3/8/8/3pP3/8/8/8/4K3", "e5d6"), "exd6");
+        assert_eq!(san("r3k2r/8/8/8/8/8/8/R3K2R", "e1g1"), "O-O");
+        assert_eq!(san("r3k2r/8/8/8/8/8/8/R3K2R", "e8c8"), "O-O-O");
+        
+        assert_eq!(san("8/4P3/7k/8/8/8/8/4K3", "e7e8"), "e8=Q");
+        let underpromotion = Move { promotion_piece: Some(PieceType::Knight), ..mv("e7e8") };
+        assert_eq!(Board::from_fen("8/4P3/7k/8/8/8/8/4K3").unwrap().move_to_san(&underpromotion), "e8=N");
+        
+        assert_eq!(san("4k3/8/8/8/8/8/8/R3K3", "a1a8"), "Ra8+");
+        assert_eq!(san("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR", "h5f7"), "Qxf7#");
+        // Taking the checking pawn en passant is an escape, so this is only check
+        assert_eq!(san("1r5k/1pb5/8/2Pn4/K7/7r/8/8", "b7b5"), "b5+");
+    }
+    
+    #[test]
+    fn test_san_to_move() {
+        let mv = |text: &str| Move {
+            from: Position::from_notation(&text[..2]).unwrap(),
+            to: Position::from_notation(&text[2..4]).unwrap(),
+            promotion_piece: None,
+        };
+        let parse = |fen: &str, san: &str, side: Color| Board::from_fen(fen).unwrap().san_to_move(san, side);
+        
+        let knights = "4k3/8/8/8/8/5N2/8/1N2K3";
+        assert_eq!(parse(knights, "Nbd2", Color::White), Ok(mv("b1d2")));
+        assert_eq!(parse(knights, "Nfd2+", Color::White), Ok(mv("f3d2")));
+        assert_eq!(parse(knights, "Nd2", Color::White), Err(SanError::Ambiguous(vec![mv("b1d2"), mv("f3d2")])));
+        assert_eq!(parse(knights, "Nd2", Color::Black), Err(SanError::NoMatchingMove));
+        assert_eq!(parse(knights, "Ng6", Color::White), Err(SanError::NoMatchingMove));
+        assert_eq!(parse("4k3/8/8/6N1/8/8/8/4K1N1", "N5f3", Color::White), Ok(mv("g5f3")));
+        assert_eq!(parse("4k3/8/8/8/8/Q7/8/Q1Q1K3", "Qa1b2", Color::White), Ok(mv("a1b2")));
+        // The pinned knight can't go to c3, so the other one is meant
+        assert_eq!(parse("4r1k1/8/8/8/8/8/4N3/1N2K3", "Nc3", Color::White), Ok(mv("b1c3")));
+        
+        assert_eq!(parse("4k3/8/8/3pP3/8/8/8/4K3", "exd6", Color::White), Ok(mv("e5d6")));
+        assert_eq!(parse("r3k2r/8/8/8/8/8/8/R3K2R", "O-O", Color::White), Ok(mv("e1g1")));
+        assert_eq!(parse("r3k2r/8/8/8/8/8/8/R3K2R", "O-O-O#", Color::Black), Ok(mv("e8c8")));
+        assert_eq!(parse("r3k3/8/8/8/8/8/8/R3K2R", "O-O", Color::Black), Err(SanError::NoMatchingMove));
+        
+        let promotion = parse("8/4P3/7k/8/8/8/8/4K3", "e8=N", Color::White).unwrap();
+        assert_eq!(promotion, Move { promotion_piece: Some(PieceType::Knight), ..mv("e7e8") });
+        assert_eq!(parse("8/4P3/7k/8/8/8/8/4K3", "e9", Color::White), Err(SanError::InvalidSyntax));
+        
+        // Every move written by move_to_san reads back as itself
+        let board = Board::new_game();
+        for (&from, _) in board.pieces.iter().filter(|(_, piece)| piece.color == Color::White) {
+            for to in board.get_valid_moves(&from) {
+                let game_move = Move { from, to, promotion_piece: None };
+                assert_eq!(board.san_to_move(&board.move_to_san(&game_move), Color::White), Ok(game_move));
+            }
+        }
+    }
 }
 
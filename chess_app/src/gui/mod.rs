@@ -10,17 +10,12 @@ use resvg::FitTo;
 use std::fs;
 use crate::types::{Position, Color, PieceType, Piece};
 use crate::state::GameState;
-
-#[derive(Debug, Clone)]
-pub enum Difficulty {
-    Beginner,
-    Intermediate,
-    Advanced,
-}
+use crate::ai::{AnalysisLine, Difficulty};
 
 #[derive(Debug, Clone)]
 pub enum Screen {
     MainMenu,
+    NewGameDialog,
     Game,
 }
 
@@ -28,16 +23,30 @@ pub enum Screen {
 pub struct GuiState {
     pub screen: Screen,
     pub selected_difficulty: Difficulty,
+    pub selected_color: Color,
     pub selected_square: Option<Position>,
+    /// Engine lines for the position shown, if analyzed
+    pub analysis: Vec<AnalysisLine>,
+    /// Whether an analysis is running in the background
+    pub analyzing: bool,
+    /// A brief message for the player, such as a declined draw offer
+    pub notice: Option<String>,
 }
 
 #[derive(Debug, Clone)]
 pub enum GuiMessage {
     NewGame,
     SetDifficulty(Difficulty),
+    SetHumanColor(Color),
+    StartGame { human_color: Color, difficulty: Difficulty },
     LoadGame,
     BackToMenu,
     SquareSelected(Position),
+    Analyze,
+    /// Finished analysis of the position with the given hash
+    AnalysisReady { position: u64, lines: Vec<AnalysisLine> },
+    Resign,
+    OfferDraw,
 }
 
 // Create a custom style for chess squares
@@ -45,6 +54,10 @@ pub enum GuiMessage {
 pub struct ChessSquareStyle {
     is_dark: bool,
     is_selected: bool,
+    /// The selected pawn would promote by moving here
+    is_promotion_target: bool,
+    /// A king in check stands here
+    is_checked_king: bool,
 }
 
 impl container::StyleSheet for ChessSquareStyle {
@@ -59,8 +72,20 @@ impl container::StyleSheet for ChessSquareStyle {
             IcedColor::from_rgb(1.0, 0.9, 0.7)
         };
 
+        // Outline promotion squares in gold so the player is warned before moving there,
+        // and a king in check in red
+        let (border_width, border_color) = if self.is_promotion_target {
+            (3.0, IcedColor::from_rgb(0.85, 0.65, 0.1))
+        } else if self.is_checked_king {
+            (3.0, IcedColor::from_rgb(0.9, 0.1, 0.1))
+        } else {
+            (0.0, IcedColor::TRANSPARENT)
+        };
+
         container::Appearance {
             background: Some(background.into()),
+            border_width,
+            border_color,
             ..Default::default()
         }
     }
@@ -157,13 +182,18 @@ impl GuiState {
         GuiState {
             screen: Screen::MainMenu,
             selected_difficulty: Difficulty::Beginner,
+            selected_color: Color::White,
             selected_square: None,
+            analysis: Vec::new(),
+            analyzing: false,
+            notice: None,
         }
     }
 
     pub fn view(&self, game_state: Option<&GameState>) -> Element<GuiMessage> {
         match self.screen {
             Screen::MainMenu => self.view_main_menu(),
+            Screen::NewGameDialog => self.view_new_game_dialog(),
             Screen::Game => {
                 if let Some(game_state) = game_state {
                     self.view_game(game_state)
@@ -194,6 +224,37 @@ impl GuiState {
             .width(Length::Fixed(200.0))
             .on_press(GuiMessage::LoadGame);
 
+        let content = column![
+            title,
+            new_game_button,
+            load_game_button,
+        ]
+        .spacing(20)
+        .align_items(Alignment::Center);
+
+        container(content)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .center_y()
+            .into()
+    }
+
+    fn view_new_game_dialog(&self) -> Element<GuiMessage> {
+        let title = text("New Game")
+            .size(40)
+            .width(Length::Fill)
+            .horizontal_alignment(iced::alignment::Horizontal::Center);
+
+        let color_row = row![
+            button("White")
+                .on_press(GuiMessage::SetHumanColor(Color::White)),
+            button("Black")
+                .on_press(GuiMessage::SetHumanColor(Color::Black)),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
         let difficulty_row = row![
             button("Beginner")
                 .on_press(GuiMessage::SetDifficulty(Difficulty::Beginner)),
@@ -205,12 +266,31 @@ impl GuiState {
         .spacing(10)
         .align_items(Alignment::Center);
 
+        let summary = text(format!(
+            "Playing as {:?} against a {:?} AI",
+            self.selected_color, self.selected_difficulty
+        ))
+        .size(20);
+
+        let start_button = button("Start Game")
+            .width(Length::Fixed(200.0))
+            .on_press(GuiMessage::StartGame {
+                human_color: self.selected_color,
+                difficulty: self.selected_difficulty.clone(),
+            });
+
+        let back_button = button("Back to Menu")
+            .on_press(GuiMessage::BackToMenu);
+
         let content = column![
             title,
-            new_game_button,
-            load_game_button,
+            text("Play as:").size(20),
+            color_row,
             text("Select Difficulty:").size(20),
             difficulty_row,
+            summary,
+            start_button,
+            back_button,
         ]
         .spacing(20)
         .align_items(Alignment::Center);
@@ -226,19 +306,38 @@ impl GuiState {
     fn view_game(&self, game_state: &GameState) -> Element<GuiMessage> {
         let mut board_container = Column::new().spacing(0);
         
+        // Show the board from the human player's side
+        let (ranks, files): (Vec<u8>, Vec<u8>) = match self.selected_color {
+            Color::White => ((0..8).rev().collect(), (0..8).collect()),
+            Color::Black => ((0..8).collect(), (0..8).rev().collect()),
+        };
+        
+        let promotion_squares = self.selected_square
+            .map(|from| game_state.promotion_squares(from))
+            .unwrap_or_default();
+        let checked_kings: Vec<Position> = [Color::White, Color::Black]
+            .into_iter()
+            .filter(|&color| game_state.board.is_king_in_check(color))
+            .filter_map(|color| game_state.board.king_position(color))
+            .collect();
+        
         // Create the board rows
-        for rank in (0..8).rev() {
+        for &rank in &ranks {
             let mut board_row = Row::new().spacing(0);
             
-            for file in 0..8 {
-                let pos = Position::new(file, rank);
-                let is_dark = (rank + file) % 2 == 1;
+            for &file in &files {
+                let pos = Position::new_unchecked(file, rank);
+                let is_dark = pos.square_color() == Color::Black;
                 let is_selected = self.selected_square == Some(pos);
+                let is_promotion_target = promotion_squares.contains(&pos);
+                let is_checked_king = checked_kings.contains(&pos);
                 
                 // Create a proper style struct
                 let square_style = ChessSquareStyle {
                     is_dark,
                     is_selected,
+                    is_promotion_target,
+                    is_checked_king,
                 };
                 
                 // Use image widget instead of text for pieces
@@ -289,6 +388,9 @@ impl GuiState {
                         
                         txt.into()
                     }
+                } else if is_promotion_target {
+                    // A crown on empty squares where the selected pawn would promote
+                    text("♛").size(24).style(IcedColor::from_rgb(0.85, 0.65, 0.1)).into()
                 } else {
                     text("").into()
                 };
@@ -313,12 +415,51 @@ impl GuiState {
         let back_button = button("Back to Menu")
             .on_press(GuiMessage::BackToMenu);
         
-        column![
+        // Resigning or offering a draw only makes sense while the game is on
+        let mut resign_button = button("Resign");
+        let draw_label = if game_state.can_claim_draw() { "Claim Draw" } else { "Offer Draw" };
+        let mut draw_button = button(draw_label);
+        if !game_state.is_game_over() {
+            resign_button = resign_button.on_press(GuiMessage::Resign);
+            draw_button = draw_button.on_press(GuiMessage::OfferDraw);
+        }
+        let game_actions = row![resign_button, draw_button, back_button].spacing(10);
+        
+        let board_with_analysis = row![
             board_container,
-            back_button,
+            self.view_analysis(),
         ]
-        .spacing(20)
-        .align_items(Alignment::Center)
-        .into()
+        .spacing(20);
+        
+        let mut content = column![text(game_state.status.describe()).size(24)];
+        if let Some(notice) = &self.notice {
+            content = content.push(text(notice).size(18));
+        }
+        
+        content
+            .push(board_with_analysis)
+            .push(game_actions)
+            .spacing(20)
+            .align_items(Alignment::Center)
+            .into()
+    }
+
+    fn view_analysis(&self) -> Element<GuiMessage> {
+        let mut analyze_button = button(if self.analyzing { "Analyzing..." } else { "Analyze" })
+            .width(Length::Fixed(160.0));
+        if !self.analyzing {
+            analyze_button = analyze_button.on_press(GuiMessage::Analyze);
+        }
+        
+        let mut panel = Column::new()
+            .spacing(10)
+            .push(text("Analysis").size(24))
+            .push(analyze_button);
+        
+        for (index, line) in self.analysis.iter().enumerate() {
+            panel = panel.push(text(format!("{}. {} ({:+.2})", index + 1, line.san, line.evaluation as f32 / 100.0)).size(20));
+        }
+        
+        panel.into()
     }
 }
@@ -11,6 +11,14 @@
 //! - `types`: Core type definitions (Color, PieceType, Piece, Position)
 //! - `board`: Chess board implementation with move validation
 //! - `state`: Game state management and turn tracking
+//! - `ai`: Computer opponent and its difficulty levels
+//! - `net`: Wire encoding of moves and a session for remote two-player games
+//! - `player`: The players of a game and a driver alternating between them
+//! - `zobrist`: Keys for hashing positions
+//!
+//! ## Features
+//!
+//! - `parallel`: Adds `GameState::perft_parallel`, which splits perft across threads with rayon
 //!
 //! ## Usage
 //!
@@ -24,8 +32,8 @@
 //! let mut game = GameState::new();
 //! 
 //! // Make a move from e2 to e4
-//! let from = Position::new(4, 1);
-//! let to = Position::new(4, 3);
+//! let from = Position::new(4, 1).unwrap();
+//! let to = Position::new(4, 3).unwrap();
 //! 
 //! if board.make_move(&from, &to) {
 //!     println!("Moved piece from e2 to e4");
@@ -36,10 +44,10 @@
 pub mod types;
 pub mod board;
 pub mod state;
-
-// Test configuration
-#[cfg(test)]
-mod tests;
+pub mod ai;
+pub mod net;
+pub mod player;
+pub mod zobrist;
 
 // Re-export common types for easier access
 pub use types::{Color, Piece, PieceType, Position};
@@ -2,24 +2,30 @@ mod gui;
 mod state;
 mod board;
 mod types;
+mod zobrist;
 // Comment out the rules module which duplicates types
 // mod rules;
 mod ai;
 
-use ai::ChessAI;
-use types::{Color, Position};
+use std::sync::{Arc, Mutex};
+
+use ai::{ChessAI, PonderHandle};
+use types::Position;
 use iced::{
     executor, window, Application, Element, Settings, Theme,
     Command,
 };
 
 use gui::{GuiState, GuiMessage, Screen};
-use state::GameState;
+use state::{GameEvent, GameState, Move};
 
 pub struct ChessApp {
     gui_state: GuiState,
     game_state: Option<GameState>,
     chess_ai: Option<ChessAI>,
+    ponder: Option<PonderHandle>,
+    /// Events from the game's observer that the screen hasn't reacted to yet
+    events: Arc<Mutex<Vec<GameEvent>>>,
 }
 
 impl Application for ChessApp {
@@ -34,6 +40,8 @@ impl Application for ChessApp {
                 gui_state: GuiState::new(),
                 game_state: None,
                 chess_ai: None,
+                ponder: None,
+                events: Arc::new(Mutex::new(Vec::new())),
             },
             Command::none(),
         )
@@ -46,125 +54,110 @@ impl Application for ChessApp {
     fn update(&mut self, message: GuiMessage) -> Command<GuiMessage> {
         match message {
             GuiMessage::NewGame => {
-                self.game_state = Some(GameState::new());
-                self.gui_state.screen = Screen::Game;
-                self.chess_ai = Some(ChessAI::new(Color::Black, self.gui_state.selected_difficulty.clone()));
+                self.gui_state.screen = Screen::NewGameDialog;
             }
             GuiMessage::SetDifficulty(difficulty) => {
                 self.gui_state.selected_difficulty = difficulty;
             }
+            GuiMessage::SetHumanColor(color) => {
+                self.gui_state.selected_color = color;
+            }
+            GuiMessage::StartGame { human_color, difficulty } => {
+                let mut game_state = GameState::new();
+                let events = Arc::clone(&self.events);
+                game_state.set_observer(move |event| events.lock().unwrap().push(*event));
+                self.game_state = Some(game_state);
+                self.gui_state.screen = Screen::Game;
+                self.gui_state.selected_color = human_color;
+                self.gui_state.selected_square = None;
+                self.gui_state.analysis.clear();
+                self.gui_state.notice = None;
+                self.chess_ai = Some(ChessAI::new(human_color.opposite(), difficulty));
+                self.ponder = None;
+                
+                // White moves first, so the AI opens when the player takes Black
+                self.play_ai_move(None);
+            }
+            GuiMessage::Analyze => {
+                if let Some(game_state) = &self.game_state {
+                    let game_state = game_state.clone();
+                    let analyst = ChessAI::new(game_state.current_player, self.gui_state.selected_difficulty.clone());
+                    self.gui_state.analyzing = true;
+                    
+                    // Search in the background so the board stays responsive
+                    return Command::perform(
+                        async move {
+                            let lines = analyst.analyze(&game_state, 3);
+                            (game_state.hash(), lines)
+                        },
+                        |(position, lines)| GuiMessage::AnalysisReady { position, lines },
+                    );
+                }
+            }
+            GuiMessage::AnalysisReady { position, lines } => {
+                self.gui_state.analyzing = false;
+                
+                // Drop results for a position that has since changed
+                if self.game_state.as_ref().map(|game_state| game_state.hash()) == Some(position) {
+                    self.gui_state.analysis = lines;
+                }
+            }
+            GuiMessage::Resign => {
+                if let Some(game_state) = &mut self.game_state {
+                    if !game_state.is_game_over() {
+                        game_state.resign(self.gui_state.selected_color);
+                        self.gui_state.notice = None;
+                        self.ponder = None;
+                    }
+                }
+            }
+            GuiMessage::OfferDraw => {
+                if let (Some(game_state), Some(chess_ai)) = (&mut self.game_state, &self.chess_ai) {
+                    if !game_state.is_game_over() {
+                        // A draw the human may claim needs no agreement
+                        let claimed = game_state.claim_draw().is_some();
+                        if claimed || chess_ai.should_accept_draw(game_state) {
+                            if !claimed {
+                                game_state.agree_draw();
+                            }
+                            self.gui_state.notice = None;
+                            self.ponder = None;
+                        } else {
+                            self.gui_state.notice = Some(String::from("The AI declined the draw offer"));
+                        }
+                    }
+                }
+            }
             GuiMessage::LoadGame => {
                 // TODO: Implement game loading
             }
             GuiMessage::BackToMenu => {
                 self.gui_state.screen = Screen::MainMenu;
+                self.gui_state.analysis.clear();
+                self.gui_state.notice = None;
                 self.game_state = None;
                 self.chess_ai = None;
+                self.ponder = None;
             }
             GuiMessage::SquareSelected(pos) => {
                 // Handle square selection for moves
                 if let Some(selected) = self.gui_state.selected_square {
-                    // Attempt to make a move if a square was already selected
-                    if let Some(game_state) = &mut self.game_state {
-                        if game_state.board.is_valid_move(&selected, &pos) {
-                            // Get the captured piece before making the move
-                            let captured_piece = game_state.board.get_piece(&pos).cloned();
-                            
-                            // Make the move
-                            game_state.board.make_move(&selected, &pos);
-                            
-                            // Captured piece is already handled by the make_move function
-                            
-                            // Record the move in game state
-                            game_state.record_move(selected, pos, None);
-                            
-                            // Check if opponent's king is in check after the move
-                            let opponent_color = game_state.current_player.opposite();
-                            let is_check = game_state.board.is_king_in_check(opponent_color);
-                            
-                            // Switch turns
-                            game_state.switch_turn();
-                            
-                            // Update game status based on check state
-                            if is_check {
-                                // Check if it's checkmate by seeing if opponent has any valid moves
-                                let is_checkmate = Self::is_checkmate(&game_state.board, opponent_color);
-                                
-                                if is_checkmate {
-                                    game_state.status = state::GameStatus::Checkmate { 
-                                        winner: opponent_color.opposite() 
-                                    };
-                                } else {
-                                    game_state.status = state::GameStatus::Check { 
-                                        player: opponent_color 
-                                    };
-                                }
-                            } else {
-                                // Check for stalemate
-                                let is_stalemate = Self::is_stalemate(&game_state.board, opponent_color);
-                                if is_stalemate {
-                                    game_state.status = state::GameStatus::Stalemate;
-                                } else {
-                                    game_state.status = state::GameStatus::InProgress;
-                                }
-                            }
-                            
-                            // If it's now the AI's turn (Black), make an AI move
-                            if game_state.current_player == Color::Black && 
-                               game_state.status == state::GameStatus::InProgress ||
-                               matches!(game_state.status, state::GameStatus::Check { player: Color::Black }) {
-                                if let Some(chess_ai) = &self.chess_ai {
-                                    if let Some((from, to)) = chess_ai.get_best_move(game_state) {
-                                        // Make the AI's move
-                                        if game_state.board.is_valid_move(&from, &to) {
-                                            // Get the captured piece before making the move
-                                            let captured_piece = game_state.board.get_piece(&to).cloned();
-                                            
-                                            // Make the move
-                                            game_state.board.make_move(&from, &to);
-                                            
-                                            // Captured piece is already handled by the make_move function
-                                            
-                                            // Record the move in game state
-                                            game_state.record_move(from, to, None);
-                                            
-                                            // Check if opponent's king is in check after the move
-                                            let opponent_color = game_state.current_player.opposite();
-                                            let is_check = game_state.board.is_king_in_check(opponent_color);
-                                            
-                                            // Switch back to player's turn
-                                            game_state.switch_turn();
-                                            
-                                            // Update game status based on check state
-                                            if is_check {
-                                                // Check if it's checkmate by seeing if opponent has any valid moves
-                                                let is_checkmate = Self::is_checkmate(&game_state.board, opponent_color);
-                                                
-                                                if is_checkmate {
-                                                    game_state.status = state::GameStatus::Checkmate { 
-                                                        winner: opponent_color.opposite() 
-                                                    };
-                                                } else {
-                                                    game_state.status = state::GameStatus::Check { 
-                                                        player: opponent_color 
-                                                    };
-                                                }
-                                            } else {
-                                                // Check for stalemate
-                                                let is_stalemate = Self::is_stalemate(&game_state.board, opponent_color);
-                                                if is_stalemate {
-                                                    game_state.status = state::GameStatus::Stalemate;
-                                                } else {
-                                                    game_state.status = state::GameStatus::InProgress;
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
+                    // Attempt to make a move if a square was already selected. The game state
+                    // checks whose turn it is, moves the rook when castling, switches turns
+                    // and updates the status
+                    let game_move = Move { from: selected, to: pos, promotion_piece: None };
+                    let moved = match &mut self.game_state {
+                        Some(game_state) => !game_state.is_game_over() && game_state.make_move(&game_move).is_ok(),
+                        None => false,
+                    };
+                    
+                    // Let the AI reply to the player's move
+                    if moved {
+                        self.play_ai_move(Some((selected, pos)));
                     }
                     self.gui_state.selected_square = None;
+                    self.gui_state.analysis.clear();
+                    self.gui_state.notice = None;
                 } else {
                     // Select the square if it contains a piece of the current player
                     if let Some(game_state) = &self.game_state {
@@ -177,6 +170,8 @@ impl Application for ChessApp {
                 }
             }
         }
+        
+        self.show_events();
         Command::none()
     }
 
@@ -186,72 +181,49 @@ impl Application for ChessApp {
 }
 
 impl ChessApp {
-    /// Check if a player is in checkmate (static function)
-    fn is_checkmate(board: &board::Board, player_color: Color) -> bool {
-        // If the king is not in check, it's not checkmate
-        if !board.is_king_in_check(player_color) {
-            return false;
-        }
-        
-        // Check if any move can get the king out of check
-        for from_rank in 0..8 {
-            for from_file in 0..8 {
-                let from = Position::new(from_file, from_rank);
-                
-                if let Some(piece) = board.get_piece(&from) {
-                    if piece.color == player_color {
-                        // Try all possible destination squares
-                        for to_rank in 0..8 {
-                            for to_file in 0..8 {
-                                let to = Position::new(to_file, to_rank);
-                                
-                                // If we find a valid move, not checkmate
-                                if board.is_valid_move(&from, &to) {
-                                    return false;
-                                }
-                            }
-                        }
-                    }
+    /// Turn the events the game reported since the last update into a notice for the player
+    fn show_events(&mut self) {
+        for event in self.events.lock().unwrap().drain(..) {
+            match event {
+                GameEvent::Check(player) => {
+                    self.gui_state.notice = Some(format!("Check! {:?} is in check", player));
+                }
+                GameEvent::Checkmate(winner) => {
+                    self.gui_state.notice = Some(format!("Checkmate! {:?} wins", winner));
                 }
+                _ => {}
             }
         }
-        
-        // If no valid moves found, it's checkmate
-        true
     }
     
-    /// Check if a player is in stalemate (static function)
-    fn is_stalemate(board: &board::Board, player_color: Color) -> bool {
-        // If the king is in check, it's not stalemate
-        if board.is_king_in_check(player_color) {
-            return false;
+    /// Make the AI's move if it's the AI's turn and the game isn't over
+    ///
+    /// `human_move` is the move the player just made, used to pick up a pondered search.
+    fn play_ai_move(&mut self, human_move: Option<(Position, Position)>) {
+        let (game_state, chess_ai) = match (&mut self.game_state, &self.chess_ai) {
+            (Some(game_state), Some(chess_ai)) => (game_state, chess_ai),
+            _ => return,
+        };
+        
+        // Every draw ends the game, not just mate
+        let ai_to_move = game_state.current_player == chess_ai.color() && !game_state.is_game_over();
+        if !ai_to_move {
+            return;
         }
         
-        // Check if the player has any valid moves
-        for from_rank in 0..8 {
-            for from_file in 0..8 {
-                let from = Position::new(from_file, from_rank);
-                
-                if let Some(piece) = board.get_piece(&from) {
-                    if piece.color == player_color {
-                        // Try all possible destination squares
-                        for to_rank in 0..8 {
-                            for to_file in 0..8 {
-                                let to = Position::new(to_file, to_rank);
-                                
-                                // If we find a valid move, not stalemate
-                                if board.is_valid_move(&from, &to) {
-                                    return false;
-                                }
-                            }
-                        }
-                    }
-                }
+        // Reuse the background search if it has finished and the player made the predicted move
+        let pondered = self.ponder.take()
+            .zip(human_move)
+            .and_then(|(handle, played)| handle.reply_to(played));
+        
+        if let Some((from, to)) = pondered.or_else(|| chess_ai.get_best_move(game_state)) {
+            // Making the move through the game state also moves the rook when castling,
+            // switches back to the player's turn and updates the status
+            if game_state.make_move(&Move { from, to, promotion_piece: None }).is_ok() {
+                // Start thinking about the reply to the player's next move
+                self.ponder = chess_ai.ponder(game_state);
             }
         }
-        
-        // If no valid moves found, it's stalemate
-        true
     }
 }
 
@@ -270,3 +242,159 @@ pub fn main() -> iced::Result {
         ..Default::default()
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai::Difficulty;
+    use board::Board;
+    use state::{DrawReason, GameStatus};
+    use types::{Color, Piece, PieceType};
+
+    /// Run a message through the app, dropping the command it returns since tests don't
+    /// execute commands
+    fn send(app: &mut ChessApp, message: GuiMessage) {
+        let _ = app.update(message);
+    }
+
+    #[test]
+    fn test_start_as_black_ai_moves_first() {
+        let (mut app, _) = ChessApp::new(());
+        send(&mut app, GuiMessage::StartGame {
+            human_color: Color::Black,
+            difficulty: Difficulty::Beginner,
+        });
+
+        let game_state = app.game_state.as_ref().unwrap();
+        assert_eq!(game_state.current_player, Color::Black);
+
+        let opening = game_state.get_last_move().expect("AI should have played the first move");
+        assert_eq!(game_state.board.get_piece(&opening.to).unwrap().color, Color::White);
+        
+        // The board is shown from Black's side and resigning gives the game to White
+        assert_eq!(app.gui_state.selected_color, Color::Black);
+        send(&mut app, GuiMessage::Resign);
+        let game_state = app.game_state.as_ref().unwrap();
+        assert_eq!(game_state.status, GameStatus::Resigned { winner: Color::White });
+    }
+
+    #[test]
+    fn test_start_as_white_waits_for_player() {
+        let (mut app, _) = ChessApp::new(());
+        send(&mut app, GuiMessage::StartGame {
+            human_color: Color::White,
+            difficulty: Difficulty::Beginner,
+        });
+
+        let game_state = app.game_state.as_ref().unwrap();
+        assert_eq!(game_state.current_player, Color::White);
+        assert!(game_state.get_last_move().is_none());
+    }
+
+    #[test]
+    fn test_ai_not_asked_to_move_after_draw() {
+        let (mut app, _) = ChessApp::new(());
+        send(&mut app, GuiMessage::StartGame {
+            human_color: Color::White,
+            difficulty: Difficulty::Beginner,
+        });
+        
+        // Taking Black's last pawn leaves a lone knight, which can't mate
+        let game_state = app.game_state.as_mut().unwrap();
+        game_state.board = Board::new();
+        game_state.board.set_piece(Position::new_unchecked(4, 0), Piece::new(PieceType::King, Color::White));
+        game_state.board.set_piece(Position::new_unchecked(2, 3), Piece::new(PieceType::Knight, Color::White));
+        game_state.board.set_piece(Position::new_unchecked(4, 7), Piece::new(PieceType::King, Color::Black));
+        game_state.board.set_piece(Position::new_unchecked(3, 5), Piece::new(PieceType::Pawn, Color::Black));
+        
+        send(&mut app, GuiMessage::SquareSelected(Position::new_unchecked(2, 3)));
+        send(&mut app, GuiMessage::SquareSelected(Position::new_unchecked(3, 5)));
+        
+        let game_state = app.game_state.as_ref().unwrap();
+        assert!(game_state.is_game_over());
+        assert_eq!(game_state.current_player, Color::Black);
+        assert_eq!(game_state.get_last_move().unwrap().to, Position::new_unchecked(3, 5));
+    }
+    
+    #[test]
+    fn test_draw_offer_accepted_only_when_ai_is_not_ahead() {
+        let (mut app, _) = ChessApp::new(());
+        send(&mut app, GuiMessage::StartGame {
+            human_color: Color::White,
+            difficulty: Difficulty::Beginner,
+        });
+        
+        // The starting position is level, so the AI agrees
+        send(&mut app, GuiMessage::OfferDraw);
+        let game_state = app.game_state.as_ref().unwrap();
+        assert_eq!(game_state.status, GameStatus::Draw(DrawReason::Agreement));
+        assert!(game_state.is_game_over());
+        assert!(app.gui_state.notice.is_none());
+        
+        // Without White's queen the AI is well ahead and plays on
+        send(&mut app, GuiMessage::StartGame {
+            human_color: Color::White,
+            difficulty: Difficulty::Beginner,
+        });
+        app.game_state.as_mut().unwrap().board.remove_piece(&Position::new_unchecked(3, 0));
+        send(&mut app, GuiMessage::OfferDraw);
+        
+        let game_state = app.game_state.as_ref().unwrap();
+        assert!(!game_state.is_game_over());
+        assert!(app.gui_state.notice.is_some());
+        
+        send(&mut app, GuiMessage::Resign);
+        let game_state = app.game_state.as_ref().unwrap();
+        assert_eq!(game_state.status, GameStatus::Resigned { winner: Color::Black });
+        
+        // A draw the human may claim is granted however well the AI stands
+        app.game_state = GameState::from_fen("3qk3/8/8/8/8/8/8/4K3 w - - 100 80");
+        send(&mut app, GuiMessage::OfferDraw);
+        let game_state = app.game_state.as_ref().unwrap();
+        assert_eq!(game_state.status, GameStatus::Draw(DrawReason::FiftyMoveRule));
+    }
+    
+    #[test]
+    fn test_check_event_shows_notice() {
+        let (mut app, _) = ChessApp::new(());
+        send(&mut app, GuiMessage::StartGame {
+            human_color: Color::Black,
+            difficulty: Difficulty::Beginner,
+        });
+        
+        // Replace the AI's opening with a line where White's next move gives check
+        let game_state = app.game_state.as_mut().unwrap();
+        game_state.undo();
+        for san in ["e4", "f5"] {
+            game_state.apply_san(san).unwrap();
+        }
+        app.events.lock().unwrap().clear();
+        app.gui_state.notice = None;
+        
+        app.game_state.as_mut().unwrap().apply_san("Qh5+").unwrap();
+        assert_eq!(app.events.lock().unwrap().last(), Some(&GameEvent::Check(Color::Black)));
+        
+        app.show_events();
+        assert_eq!(app.gui_state.notice.as_deref(), Some("Check! Black is in check"));
+        assert!(app.events.lock().unwrap().is_empty());
+    }
+    
+    #[test]
+    fn test_gui_rejects_move_of_wrong_color() {
+        let (mut app, _) = ChessApp::new(());
+        send(&mut app, GuiMessage::StartGame {
+            human_color: Color::White,
+            difficulty: Difficulty::Beginner,
+        });
+        
+        // Force a Black pawn to be selected while White is to move
+        app.gui_state.selected_square = Some(Position::new_unchecked(4, 6));
+        send(&mut app, GuiMessage::SquareSelected(Position::new_unchecked(4, 4)));
+        
+        let game_state = app.game_state.as_ref().unwrap();
+        assert_eq!(game_state.board, Board::new_game());
+        assert_eq!(game_state.current_player, Color::White);
+        assert!(game_state.get_last_move().is_none());
+        assert!(app.gui_state.selected_square.is_none());
+    }
+}
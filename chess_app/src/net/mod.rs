@@ -0,0 +1,224 @@
+use crate::state::{GameState, Move, MoveError};
+use crate::types::{Color, PieceType, Position};
+
+/// Size in bytes of an encoded move
+pub const MOVE_MESSAGE_LEN: usize = 3;
+
+/// Why a wire message couldn't be decoded into a move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The message isn't `MOVE_MESSAGE_LEN` bytes long
+    WrongLength(usize),
+    /// A square index is outside 0-63
+    InvalidSquare(u8),
+    /// The promotion byte doesn't name a piece a pawn can promote to
+    InvalidPromotion(u8),
+}
+
+/// Why a session refused a move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionError {
+    Decode(DecodeError),
+    /// The move came from the side that isn't on move
+    OutOfTurn,
+    /// The move isn't legal in the current position
+    IllegalMove(MoveError),
+    /// The game has already ended, so no more moves are accepted
+    GameOver,
+}
+
+fn encode_square(pos: Position) -> u8 {
+    pos.rank * 8 + pos.file
+}
+
+fn decode_square(byte: u8) -> Result<Position, DecodeError> {
+    Position::new(byte % 8, byte / 8).ok_or(DecodeError::InvalidSquare(byte))
+}
+
+/// Encode a move as `[from, to, promotion]`, with squares numbered 0-63 from a1 along
+/// each rank and the promotion piece as 0 for none, then 1-4 for queen, rook, bishop, knight.
+///
+/// Castling is sent as the king's move.
+pub fn encode_move(game_move: &Move) -> [u8; MOVE_MESSAGE_LEN] {
+    let promotion = match game_move.promotion_piece {
+        None => 0,
+        Some(PieceType::Queen) => 1,
+        Some(PieceType::Rook) => 2,
+        Some(PieceType::Bishop) => 3,
+        Some(PieceType::Knight) => 4,
+        // Never legal, so the receiver rejects it
+        Some(PieceType::King | PieceType::Pawn) => u8::MAX,
+    };
+    
+    [encode_square(game_move.from), encode_square(game_move.to), promotion]
+}
+
+/// Decode a move written by `encode_move`
+pub fn decode_move(bytes: &[u8]) -> Result<Move, DecodeError> {
+    let (from, to, promotion) = match *bytes {
+        [from, to, promotion] => (from, to, promotion),
+        _ => return Err(DecodeError::WrongLength(bytes.len())),
+    };
+    
+    let promotion_piece = match promotion {
+        0 => None,
+        1 => Some(PieceType::Queen),
+        2 => Some(PieceType::Rook),
+        3 => Some(PieceType::Bishop),
+        4 => Some(PieceType::Knight),
+        other => return Err(DecodeError::InvalidPromotion(other)),
+    };
+    
+    Ok(Move {
+        from: decode_square(from)?,
+        to: decode_square(to)?,
+        promotion_piece,
+    })
+}
+
+/// One side of a two-player game played over a connection.
+///
+/// The session keeps the authoritative game and only applies legal moves from the
+/// side on move, so a misbehaving peer can't corrupt it.
+#[derive(Debug, Clone)]
+pub struct Session {
+    game: GameState,
+    local_color: Color,
+}
+
+impl Session {
+    /// Start a session from the initial position, playing `local_color` on this end
+    pub fn new(local_color: Color) -> Self {
+        Session { game: GameState::new(), local_color }
+    }
+    
+    /// The color played on this end
+    pub fn local_color(&self) -> Color {
+        self.local_color
+    }
+    
+    /// The game as agreed by both ends
+    pub fn game(&self) -> &GameState {
+        &self.game
+    }
+    
+    /// Play a local move, returning the message to send to the peer
+    pub fn send(&mut self, game_move: &Move) -> Result<[u8; MOVE_MESSAGE_LEN], SessionError> {
+        self.apply(game_move, self.local_color)?;
+        Ok(encode_move(game_move))
+    }
+    
+    /// Apply a move received from the peer, returning it
+    pub fn receive(&mut self, bytes: &[u8]) -> Result<Move, SessionError> {
+        let game_move = decode_move(bytes).map_err(SessionError::Decode)?;
+        self.apply(&game_move, self.local_color.opposite())?;
+        Ok(game_move)
+    }
+    
+    fn apply(&mut self, game_move: &Move, mover: Color) -> Result<(), SessionError> {
+        if self.game.is_game_over() {
+            return Err(SessionError::GameOver);
+        }
+        if self.game.current_player != mover {
+            return Err(SessionError::OutOfTurn);
+        }
+        
+        // Moves on the wire must be complete, including any promotion piece
+        self.game.validate_move(game_move).map_err(SessionError::IllegalMove)?;
+        self.game.make_move(game_move).map_err(SessionError::IllegalMove)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::GameStatus;
+    
+    fn mv(from: &str, to: &str, promotion_piece: Option<PieceType>) -> Move {
+        Move {
+            from: Position::from_notation(from).unwrap(),
+            to: Position::from_notation(to).unwrap(),
+            promotion_piece,
+        }
+    }
+    
+    fn relay(sender: &mut Session, receiver: &mut Session, from: &str, to: &str) {
+        let message = sender.send(&mv(from, to, None)).unwrap();
+        receiver.receive(&message).unwrap();
+    }
+    
+    #[test]
+    fn test_move_roundtrip() {
+        let moves = [
+            mv("e2", "e4", None),
+            mv("a1", "h8", None),
+            // Castling, sent as the king's move
+            mv("e1", "g1", None),
+            mv("e8", "c8", None),
+            // Every promotion piece
+            mv("b7", "b8", Some(PieceType::Queen)),
+            mv("b7", "a8", Some(PieceType::Rook)),
+            mv("g2", "g1", Some(PieceType::Bishop)),
+            mv("g2", "h1", Some(PieceType::Knight)),
+        ];
+        
+        for game_move in moves {
+            assert_eq!(decode_move(&encode_move(&game_move)), Ok(game_move));
+        }
+    }
+    
+    #[test]
+    fn test_decode_rejects_malformed() {
+        assert_eq!(decode_move(&[12, 28]), Err(DecodeError::WrongLength(2)));
+        assert_eq!(decode_move(&[12, 64, 0]), Err(DecodeError::InvalidSquare(64)));
+        assert_eq!(decode_move(&[52, 60, 5]), Err(DecodeError::InvalidPromotion(5)));
+        assert_eq!(
+            decode_move(&encode_move(&mv("b7", "b8", Some(PieceType::King)))),
+            Err(DecodeError::InvalidPromotion(u8::MAX))
+        );
+    }
+    
+    #[test]
+    fn test_session_validates_received_moves() {
+        let mut white = Session::new(Color::White);
+        let mut black = Session::new(Color::Black);
+        
+        // Black can't move first, from either end
+        let early = encode_move(&mv("e7", "e5", None));
+        assert_eq!(white.receive(&early), Err(SessionError::OutOfTurn));
+        assert_eq!(black.send(&mv("e7", "e5", None)), Err(SessionError::OutOfTurn));
+        
+        let message = white.send(&mv("e2", "e4", None)).unwrap();
+        assert_eq!(black.receive(&message), Ok(mv("e2", "e4", None)));
+        assert_eq!(black.game().board, white.game().board);
+        
+        // An illegal reply leaves the game untouched
+        let illegal = encode_move(&mv("e7", "e4", None));
+        assert_eq!(white.receive(&illegal), Err(SessionError::IllegalMove(MoveError::IllegalMove)));
+        assert_eq!(white.game().current_player, Color::Black);
+        
+        let reply = black.send(&mv("e7", "e5", None)).unwrap();
+        assert!(white.receive(&reply).is_ok());
+        assert_eq!(white.game().board, black.game().board);
+    }
+    
+    #[test]
+    fn test_session_rejects_moves_after_game_over() {
+        let mut white = Session::new(Color::White);
+        let mut black = Session::new(Color::Black);
+        
+        // Fool's mate leaves White to move but mated
+        relay(&mut white, &mut black, "f2", "f3");
+        relay(&mut black, &mut white, "e7", "e5");
+        relay(&mut white, &mut black, "g2", "g4");
+        relay(&mut black, &mut white, "d8", "h4");
+        assert_eq!(white.send(&mv("a2", "a3", None)), Err(SessionError::GameOver));
+        assert_eq!(black.receive(&encode_move(&mv("a2", "a3", None))), Err(SessionError::GameOver));
+        
+        // A resignation on one end ends the game there too
+        let mut white = Session::new(Color::White);
+        white.game.resign(Color::Black);
+        assert_eq!(white.send(&mv("e2", "e4", None)), Err(SessionError::GameOver));
+        assert_eq!(white.game().status, GameStatus::Resigned { winner: Color::White });
+    }
+}
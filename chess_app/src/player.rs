@@ -0,0 +1,246 @@
+//! Players and a driver that alternates between them.
+//!
+//! The driver is for games run outside the GUI, such as engine matches and network play.
+//! The GUI in `main.rs` still plays the human against `ChessAI` itself and doesn't use it,
+//! so choosing the players from the GUI isn't supported yet.
+
+use std::sync::mpsc::{Receiver, Sender, channel};
+
+use crate::ai::ChessAI;
+use crate::net::{decode_move, MOVE_MESSAGE_LEN};
+use crate::state::{DrawReason, GameResult, GameState, Move, MoveError};
+use crate::types::Color;
+
+/// What a player does on their turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerAction {
+    Move(Move),
+    Resign,
+    /// Claim a draw if one is available, otherwise offer one to the opponent
+    OfferDraw,
+}
+
+/// One side of a game, whether a person at the board, the AI or a remote peer.
+///
+/// Players fed from a front end or a connection can't block waiting for input, so
+/// `next_move` may return `None` and the driver asks again on a later step.
+pub trait Player {
+    /// The action to take with `game_state` on move, or `None` if the player hasn't decided yet
+    fn next_move(&mut self, game_state: &GameState) -> Option<PlayerAction>;
+    
+    /// Whether the player agrees to a draw offered by the opponent
+    fn accepts_draw(&mut self, _game_state: &GameState) -> bool {
+        false
+    }
+}
+
+impl Player for ChessAI {
+    fn next_move(&mut self, game_state: &GameState) -> Option<PlayerAction> {
+        if self.should_resign(game_state) {
+            return Some(PlayerAction::Resign);
+        }
+        if game_state.can_claim_draw() && self.should_accept_draw(game_state) {
+            return Some(PlayerAction::OfferDraw);
+        }
+        
+        let (from, to) = self.get_best_move(game_state)?;
+        Some(PlayerAction::Move(Move { from, to, promotion_piece: None }))
+    }
+    
+    fn accepts_draw(&mut self, game_state: &GameState) -> bool {
+        self.should_accept_draw(game_state)
+    }
+}
+
+/// A person at the board, whose actions arrive through a channel from whatever front end
+/// they're using.
+#[derive(Debug)]
+pub struct HumanPlayer {
+    actions: Receiver<PlayerAction>,
+}
+
+impl HumanPlayer {
+    /// Create the player along with the sender the front end uses to submit its actions
+    pub fn new() -> (Self, Sender<PlayerAction>) {
+        let (sender, actions) = channel();
+        (HumanPlayer { actions }, sender)
+    }
+}
+
+impl Player for HumanPlayer {
+    fn next_move(&mut self, _game_state: &GameState) -> Option<PlayerAction> {
+        self.actions.try_recv().ok()
+    }
+}
+
+/// A peer on the other end of a connection, whose moves arrive as wire messages.
+///
+/// Messages that don't decode are dropped; the driver checks decoded moves for legality.
+#[derive(Debug)]
+pub struct RemotePlayer {
+    messages: Receiver<[u8; MOVE_MESSAGE_LEN]>,
+}
+
+impl RemotePlayer {
+    /// Create the player along with the sender the connection feeds received messages into
+    pub fn new() -> (Self, Sender<[u8; MOVE_MESSAGE_LEN]>) {
+        let (sender, messages) = channel();
+        (RemotePlayer { messages }, sender)
+    }
+}
+
+impl Player for RemotePlayer {
+    fn next_move(&mut self, _game_state: &GameState) -> Option<PlayerAction> {
+        self.messages.try_iter()
+            .find_map(|message| decode_move(&message).ok())
+            .map(PlayerAction::Move)
+    }
+}
+
+/// The outcome of asking the player on move for an action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Turn {
+    /// The given move was played
+    Moved(Move),
+    /// The player on move hasn't decided yet, or a draw offer was declined
+    Waiting,
+    /// The game is over
+    Over(GameResult),
+}
+
+/// Runs a game between two players, asking whichever is on move for its action.
+pub struct GameDriver {
+    game_state: GameState,
+    white: Box<dyn Player>,
+    black: Box<dyn Player>,
+    result: Option<GameResult>,
+}
+
+impl GameDriver {
+    /// Start driving `game_state` between `white` and `black`
+    pub fn new(game_state: GameState, white: Box<dyn Player>, black: Box<dyn Player>) -> Self {
+        let result = game_state.get_game_result();
+        GameDriver { game_state, white, black, result }
+    }
+    
+    /// The game as played so far
+    pub fn game_state(&self) -> &GameState {
+        &self.game_state
+    }
+    
+    /// How the game ended, including by resignation or agreed draw, if it has
+    pub fn result(&self) -> Option<GameResult> {
+        self.result
+    }
+    
+    /// Ask the player on move for an action and carry it out.
+    ///
+    /// An illegal move is rejected with the game left untouched, so the player is asked again.
+    pub fn step(&mut self) -> Result<Turn, MoveError> {
+        if let Some(result) = self.result {
+            return Ok(Turn::Over(result));
+        }
+        
+        let mover = self.game_state.current_player;
+        let (player, opponent) = match mover {
+            Color::White => (&mut self.white, &mut self.black),
+            Color::Black => (&mut self.black, &mut self.white),
+        };
+        
+        let action = match player.next_move(&self.game_state) {
+            Some(action) => action,
+            None => return Ok(Turn::Waiting),
+        };
+        
+        match action {
+            PlayerAction::Move(game_move) => {
+                self.game_state.make_move(&game_move)?;
+                self.result = self.game_state.get_game_result();
+                return Ok(Turn::Moved(game_move));
+            }
+            PlayerAction::Resign => {
+                self.result = Some(GameResult::Resignation { winner: mover.opposite() });
+            }
+            PlayerAction::OfferDraw => {
+                if let Some(reason) = self.game_state.claim_draw() {
+                    self.result = Some(GameResult::Draw(reason));
+                } else if opponent.accepts_draw(&self.game_state) {
+                    self.game_state.agree_draw();
+                    self.result = Some(GameResult::Draw(DrawReason::Agreement));
+                }
+            }
+        }
+        
+        Ok(self.result.map_or(Turn::Waiting, Turn::Over))
+    }
+    
+    /// Step until the game ends, the player on move has to wait, or `max_plies` moves are made,
+    /// returning the result if the game is over
+    pub fn run(&mut self, max_plies: usize) -> Result<Option<GameResult>, MoveError> {
+        let mut plies = 0;
+        while plies < max_plies {
+            match self.step()? {
+                Turn::Moved(_) => plies += 1,
+                Turn::Waiting | Turn::Over(_) => break,
+            }
+        }
+        Ok(self.result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::Difficulty;
+    use crate::board::Board;
+    use crate::types::{Piece, PieceType, Position};
+    
+    #[test]
+    fn test_ai_players_finish_a_game() {
+        // King and queen against a lone king, which won't resign
+        let mut game = GameState::new();
+        game.board = Board::new();
+        game.board.set_piece(Position::new_unchecked(4, 0), Piece::new(PieceType::King, Color::White));
+        game.board.set_piece(Position::new_unchecked(3, 0), Piece::new(PieceType::Queen, Color::White));
+        game.board.set_piece(Position::new_unchecked(4, 7), Piece::new(PieceType::King, Color::Black));
+        game.refresh_status();
+        
+        let white = ChessAI::new(Color::White, Difficulty::Beginner);
+        let black = ChessAI::new(Color::Black, Difficulty::Beginner);
+        let mut driver = GameDriver::new(game, Box::new(white), Box::new(black));
+        
+        let result = driver.run(400).unwrap();
+        assert!(result.is_some(), "game still going after 400 plies");
+        assert!(driver.game_state().get_last_move().is_some());
+        assert_eq!(driver.step(), Ok(Turn::Over(result.unwrap())));
+    }
+    
+    #[test]
+    fn test_human_and_remote_players_wait_for_input() {
+        let (human, actions) = HumanPlayer::new();
+        let (remote, messages) = RemotePlayer::new();
+        let mut driver = GameDriver::new(GameState::new(), Box::new(human), Box::new(remote));
+        
+        assert_eq!(driver.step(), Ok(Turn::Waiting));
+        
+        let e4 = Move {
+            from: Position::from_notation("e2").unwrap(),
+            to: Position::from_notation("e4").unwrap(),
+            promotion_piece: None,
+        };
+        actions.send(PlayerAction::Move(e4)).unwrap();
+        assert_eq!(driver.step(), Ok(Turn::Moved(e4)));
+        assert_eq!(driver.step(), Ok(Turn::Waiting));
+        
+        // A garbled message is skipped, and an illegal move is rejected
+        messages.send([12, 64, 0]).unwrap();
+        messages.send([52, 28, 0]).unwrap();
+        assert_eq!(driver.step(), Err(MoveError::IllegalMove));
+        assert_eq!(driver.game_state().current_player, Color::Black);
+        
+        actions.send(PlayerAction::Resign).unwrap();
+        messages.send([52, 36, 0]).unwrap();
+        assert!(matches!(driver.step(), Ok(Turn::Moved(_))));
+        assert_eq!(driver.step(), Ok(Turn::Over(GameResult::Resignation { winner: Color::Black })));
+    }
+}
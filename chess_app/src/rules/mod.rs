@@ -124,6 +124,16 @@ impl Board {
             false
         }
     }
+
+    /// Returns the number of assets on the board
+    pub fn piece_count(&self) -> usize {
+        self.pieces.iter().flatten().filter(|square| square.is_some()).count()
+    }
+
+    /// Returns true if the board has no assets
+    pub fn is_empty(&self) -> bool {
+        self.piece_count() == 0
+    }
 }
 
 // Add helper methods for move validation
@@ -1,36 +1,240 @@
 use serde::{Deserialize, Serialize};
-use crate::board::Board;
+use crate::board::{Board, CheckType};
 use crate::types::{Color, Piece, PieceType, Position};
+use crate::zobrist;
 use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Move {
+    #[serde(with = "crate::types::algebraic")]
     pub from: Position,
+    #[serde(with = "crate::types::algebraic")]
     pub to: Position,
     pub promotion_piece: Option<PieceType>,
 }
 
+/// What a move does on the board, as classified by `GameState::move_kind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MoveKind {
+    Normal,
+    Capture,
+    /// The king moves two squares towards a rook, which jumps over it
+    Castle,
+    /// A pawn captures a pawn that has just passed it with a double step
+    EnPassant,
+    /// A pawn reaches the last rank, possibly capturing
+    Promotion,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum GameStatus {
     InProgress,
     Check { player: Color },
     Checkmate { winner: Color },
     Stalemate,
-    Draw,
+    Draw(DrawReason),
+    Resigned { winner: Color },
+}
+
+impl GameStatus {
+    /// A sentence describing the status for banners and logs, e.g. "White wins by checkmate"
+    pub fn describe(&self) -> String {
+        match self {
+            GameStatus::InProgress => String::from("Game in progress"),
+            GameStatus::Check { player } => format!("{:?} is in check", player),
+            GameStatus::Checkmate { winner } => format!("{:?} wins by checkmate", winner),
+            GameStatus::Stalemate => format!("Draw by {}", DrawReason::Stalemate.describe()),
+            GameStatus::Draw(reason) => format!("Draw by {}", reason.describe()),
+            GameStatus::Resigned { winner } => format!("{:?} wins by resignation", winner),
+        }
+    }
+}
+
+/// How a game ended in a draw, or which draw a player may claim.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DrawReason {
+    Stalemate,
+    /// Neither side can checkmate by any sequence of legal moves
+    DeadPosition,
+    /// Claimable when the same position has occurred three times
+    ThreefoldRepetition,
+    /// Automatic when the same position has occurred five times
+    FivefoldRepetition,
+    /// Claimable after fifty moves by each side without a pawn move or capture
+    FiftyMoveRule,
+    /// Automatic after seventy-five moves by each side without a pawn move or capture
+    SeventyFiveMoveRule,
+    /// Both players agreed to a draw
+    Agreement,
+}
+
+impl DrawReason {
+    /// The reason as it follows "Draw by"
+    fn describe(self) -> &'static str {
+        match self {
+            DrawReason::Stalemate => "stalemate",
+            DrawReason::DeadPosition => "dead position",
+            DrawReason::ThreefoldRepetition => "threefold repetition",
+            DrawReason::FivefoldRepetition => "fivefold repetition",
+            DrawReason::FiftyMoveRule => "the fifty-move rule",
+            DrawReason::SeventyFiveMoveRule => "the seventy-five-move rule",
+            DrawReason::Agreement => "agreement",
+        }
+    }
+}
+
+/// The final result of a game that is over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameResult {
+    Checkmate { winner: Color },
+    Resignation { winner: Color },
+    Draw(DrawReason),
+}
+
+/// A state transition reported to the observer set with `GameState::set_observer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameEvent {
+    MoveMade(Move),
+    MoveUndone(Move),
+    /// The given player is in check
+    Check(Color),
+    /// The given player won by checkmate
+    Checkmate(Color),
+    /// The given player won by the opponent's resignation
+    Resigned(Color),
+    Draw(DrawReason),
+    GameReset,
+}
+
+/// Callback receiving the events of a game.
+#[derive(Clone)]
+struct Observer(Arc<dyn Fn(&GameEvent) + Send + Sync>);
+
+impl fmt::Debug for Observer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Observer")
+    }
+}
+
+/// Which castling moves each side may still make.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CastlingRights {
+    pub white_kingside: bool,
+    pub white_queenside: bool,
+    pub black_kingside: bool,
+    pub black_queenside: bool,
+}
+
+impl CastlingRights {
+    /// Rights at the start of a game
+    pub fn all() -> Self {
+        CastlingRights {
+            white_kingside: true,
+            white_queenside: true,
+            black_kingside: true,
+            black_queenside: true,
+        }
+    }
+    
+    /// Clear the rights lost when a piece moves from or to the given square
+    fn clear_for_square(&mut self, pos: Position) {
+        match (pos.file, pos.rank) {
+            (4, 0) => {
+                self.white_kingside = false;
+                self.white_queenside = false;
+            }
+            (0, 0) => self.white_queenside = false,
+            (7, 0) => self.white_kingside = false,
+            (4, 7) => {
+                self.black_kingside = false;
+                self.black_queenside = false;
+            }
+            (0, 7) => self.black_queenside = false,
+            (7, 7) => self.black_kingside = false,
+            _ => {}
+        }
+    }
+    
+    /// Parse a FEN castling field such as "KQkq" or "-"
+    pub fn from_fen(field: &str) -> Option<Self> {
+        let mut rights = CastlingRights {
+            white_kingside: false,
+            white_queenside: false,
+            black_kingside: false,
+            black_queenside: false,
+        };
+        if field == "-" {
+            return Some(rights);
+        }
+        if field.is_empty() {
+            return None;
+        }
+        
+        for c in field.chars() {
+            match c {
+                'K' => rights.white_kingside = true,
+                'Q' => rights.white_queenside = true,
+                'k' => rights.black_kingside = true,
+                'q' => rights.black_queenside = true,
+                _ => return None,
+            }
+        }
+        Some(rights)
+    }
+    
+    /// The FEN castling field, e.g. "KQkq", or "-" when no rights remain
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        for (has_right, c) in [
+            (self.white_kingside, 'K'),
+            (self.white_queenside, 'Q'),
+            (self.black_kingside, 'k'),
+            (self.black_queenside, 'q'),
+        ] {
+            if has_right {
+                fen.push(c);
+            }
+        }
+        
+        if fen.is_empty() {
+            fen.push('-');
+        }
+        fen
+    }
 }
 
+/// A game in progress.
+///
+/// Serialized as a `CompactGame`, and rebuilt on load by replaying its moves.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "CompactGame", try_from = "CompactGame")]
 pub struct GameState {
     pub board: Board,
     pub current_player: Color,
     pub status: GameStatus,
     
+    /// Piece a pawn promotes to when a move doesn't specify one
+    pub default_promotion: PieceType,
+    
     // Track number of moves for each piece (for castling eligibility)
     piece_move_history: HashMap<Position, u32>,
     
     // Track the last move (for en passant)
     last_move: Option<Move>,
     
+    // Castling rights, cleared as kings and rooks leave their starting squares
+    castling_rights: CastlingRights,
+    
+    // Square skipped by a pawn's double step on the previous move
+    en_passant_target: Option<Position>,
+    
+    // Position keys before each move made through `make_move` (for threefold repetition)
+    position_keys: Vec<String>,
+    
     // Track promoted pawns
     promoted_pawns: HashSet<Position>,
     
@@ -39,94 +243,2625 @@ pub struct GameState {
     
     // Track captured assets
     captured_pieces: Vec<Piece>,
+    
+    // Snapshots of the state before each move made through `make_move` (for undo)
+    undo_stack: Vec<Snapshot>,
+    
+    // FEN of the position the game was started or set up from, before any recorded move
+    start_fen: String,
+    
+    // Last static evaluation, cleared whenever the position changes
+    evaluation_cache: Option<f32>,
+    
+    // Time taken for each move made through `make_move` while a clock is attached
+    move_times: Vec<Duration>,
+    
+    // Halfmoves since the last pawn move or capture
+    halfmove_clock: u32,
+    
+    // Number of the current move, starting at 1 and incremented after Black moves
+    fullmove_number: u32,
+    
+    // Clock used to time moves, and its reading when the last move was made
+    clock: Option<Arc<dyn Clock>>,
+    last_move_time: Option<Duration>,
+    
+    // Callback for state transitions, if one is registered
+    observer: Option<Observer>,
+}
+
+/// A source of time for recording how long each move took.
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Time elapsed since a fixed starting point
+    fn now(&self) -> Duration;
+}
+
+/// A `Clock` reading the system's monotonic time.
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        SystemClock { start: Instant::now() }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Errors returned when a move cannot be made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MoveError {
+    /// There is no piece on the source square
+    NoPiece,
+    /// The piece on the source square belongs to the player not on move
+    WrongTurn,
+    /// The move breaks the rules of chess
+    IllegalMove,
+    /// A pawn reaches its last rank but the move has no promotion piece
+    PromotionRequired,
+    /// The game has already ended, by mate, resignation or a draw
+    GameOver,
+}
+
+/// The consequences of a move, computed without playing it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MovePreview {
+    pub gives_check: bool,
+    pub is_capture: bool,
+    pub captured_piece: Option<Piece>,
+    pub status: GameStatus,
+}
+
+/// A game stored as its starting position and the moves played from it.
+///
+/// This is how a `GameState` is saved, rather than with a board snapshot per move.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactGame {
+    /// FEN of the starting position, including castling rights, en passant and the clocks
+    ///
+    /// Saves whose FEN lacks the clocks still load, with a halfmove clock of 0 and a fullmove
+    /// number of 1, as for `GameState::from_fen`.
+    pub initial_fen: String,
+    pub moves: Vec<Move>,
+}
+
+/// Errors returned when a `CompactGame` can't be turned back into a `GameState`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompactError {
+    /// The starting position isn't a valid FEN
+    InvalidFen,
+    /// The move at `index` couldn't be replayed
+    IllegalMove { index: usize, error: MoveError },
+}
+
+impl fmt::Display for CompactError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompactError::InvalidFen => write!(f, "invalid starting position FEN"),
+            CompactError::IllegalMove { index, error } => write!(f, "move {} can't be replayed: {:?}", index, error),
+        }
+    }
+}
+
+impl From<GameState> for CompactGame {
+    fn from(game: GameState) -> Self {
+        game.to_compact()
+    }
+}
+
+impl TryFrom<CompactGame> for GameState {
+    type Error = CompactError;
+    
+    fn try_from(compact: CompactGame) -> Result<Self, Self::Error> {
+        GameState::from_compact(&compact)
+    }
+}
+
+/// Ways a game can disagree with the moves recorded in it, found by `GameState::verify_consistency`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConsistencyError {
+    /// The recorded move at `index` in the move history can't be replayed
+    IllegalMove { index: usize, error: MoveError },
+    /// The board differs from the replayed one on these squares
+    BoardMismatch(Vec<Position>),
+    /// The replayed game has the other player to move
+    WrongPlayer,
+}
+
+/// Why a move in standard algebraic notation couldn't be played.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SanError {
+    /// The text isn't valid SAN
+    InvalidSyntax,
+    /// No legal move in the position matches
+    NoMatchingMove,
+    /// More than one legal move matches, so more disambiguation is needed; holds every
+    /// matching move so the player can be asked to pick one
+    Ambiguous(Vec<Move>),
+    /// The move matched but was rejected when played
+    IllegalMove(MoveError),
+}
+
+/// A SAN token taken apart, before it's matched against the pieces of a position.
+pub(crate) enum SanParts {
+    Castle { kingside: bool },
+    Move {
+        piece_type: PieceType,
+        from_file: Option<u8>,
+        from_rank: Option<u8>,
+        to: Position,
+        promotion_piece: Option<PieceType>,
+    },
+}
+
+impl SanParts {
+    /// Split `san` into its parts, ignoring captures and check and annotation suffixes
+    pub(crate) fn parse(san: &str) -> Result<Self, SanError> {
+        let san = san.trim_end_matches(['+', '#', '!', '?']);
+        match san {
+            "O-O" | "0-0" => return Ok(SanParts::Castle { kingside: true }),
+            "O-O-O" | "0-0-0" => return Ok(SanParts::Castle { kingside: false }),
+            _ => {}
+        }
+        
+        let piece_type_for = |c: char| match c {
+            'K' => Some(PieceType::King),
+            'Q' => Some(PieceType::Queen),
+            'R' => Some(PieceType::Rook),
+            'B' => Some(PieceType::Bishop),
+            'N' => Some(PieceType::Knight),
+            _ => None,
+        };
+        
+        let mut chars: Vec<char> = san.chars().filter(|&c| c != 'x').collect();
+        
+        // Promotion piece, written "e8=Q" or "e8Q"
+        let mut promotion_piece = None;
+        if let Some(promote_to) = chars.last().copied().and_then(piece_type_for) {
+            chars.pop();
+            if chars.last() == Some(&'=') {
+                chars.pop();
+            }
+            promotion_piece = Some(promote_to);
+        }
+        
+        let piece_type = match chars.first().copied().and_then(piece_type_for) {
+            Some(piece_type) => {
+                chars.remove(0);
+                piece_type
+            }
+            None => PieceType::Pawn,
+        };
+        if promotion_piece.is_some() && piece_type != PieceType::Pawn {
+            return Err(SanError::InvalidSyntax);
+        }
+        
+        if chars.len() < 2 || chars.len() > 4 {
+            return Err(SanError::InvalidSyntax);
+        }
+        let destination: String = chars[chars.len() - 2..].iter().collect();
+        let to = Position::from_notation(&destination).ok_or(SanError::InvalidSyntax)?;
+        
+        // Any remaining characters narrow down the moving piece's file and rank
+        let mut from_file = None;
+        let mut from_rank = None;
+        for &c in &chars[..chars.len() - 2] {
+            match c {
+                'a'..='h' if from_file.is_none() => from_file = Some(c as u8 - b'a'),
+                '1'..='8' if from_rank.is_none() => from_rank = Some(c as u8 - b'1'),
+                _ => return Err(SanError::InvalidSyntax),
+            }
+        }
+        
+        Ok(SanParts::Move { piece_type, from_file, from_rank, to, promotion_piece })
+    }
+    
+    /// The move from the only square in `candidates`, or why there isn't exactly one
+    pub(crate) fn resolve(candidates: &[Position], to: Position, promotion_piece: Option<PieceType>) -> Result<Move, SanError> {
+        match candidates {
+            [] => Err(SanError::NoMatchingMove),
+            [from] => Ok(Move { from: *from, to, promotion_piece }),
+            _ => Err(SanError::Ambiguous(
+                candidates.iter().map(|&from| Move { from, to, promotion_piece }).collect(),
+            )),
+        }
+    }
+}
+
+/// How to write moves in standard algebraic notation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SanStyle {
+    /// Write castling with zeros, "0-0", rather than the letter O
+    pub zero_castling: bool,
+    /// Append "+" to checks and "#" to mates
+    pub check_markers: bool,
+}
+
+impl Default for SanStyle {
+    fn default() -> Self {
+        SanStyle { zero_castling: false, check_markers: true }
+    }
+}
+
+/// Everything `undo` needs to restore the position before a move.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    board: Board,
+    current_player: Color,
+    status: GameStatus,
+    piece_move_history: HashMap<Position, u32>,
+    last_move: Option<Move>,
+    castling_rights: CastlingRights,
+    en_passant_target: Option<Position>,
+    promoted_pawns: HashSet<Position>,
+    captured_pieces: Vec<Piece>,
+    move_times: Vec<Duration>,
+    last_move_time: Option<Duration>,
+    halfmove_clock: u32,
+    fullmove_number: u32,
 }
 
 impl GameState {
     pub fn new() -> Self {
-        GameState {
+        let mut game = GameState {
             board: Board::new_game(),
             current_player: Color::White,
             status: GameStatus::InProgress,
+            default_promotion: PieceType::Queen,
             piece_move_history: HashMap::new(),
             last_move: None,
+            castling_rights: CastlingRights::all(),
+            en_passant_target: None,
+            position_keys: Vec::new(),
             promoted_pawns: HashSet::new(),
             move_history: Vec::new(),
             captured_pieces: Vec::new(),
+            undo_stack: Vec::new(),
+            evaluation_cache: None,
+            move_times: Vec::new(),
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            clock: None,
+            last_move_time: None,
+            observer: None,
+            start_fen: String::new(),
+        };
+        game.start_fen = game.to_fen();
+        game
+    }
+    
+    /// Register a callback that receives every event from `make_move`, `undo` and `reset`,
+    /// replacing any previous one
+    pub fn set_observer<F>(&mut self, observer: F)
+    where
+        F: Fn(&GameEvent) + Send + Sync + 'static,
+    {
+        self.observer = Some(Observer(Arc::new(observer)));
+    }
+    
+    /// Remove the registered observer
+    pub fn clear_observer(&mut self) {
+        self.observer = None;
+    }
+    
+    fn emit(&self, event: GameEvent) {
+        if let Some(observer) = &self.observer {
+            (observer.0)(&event);
         }
     }
     
-    /// Record a piece movement
-    #[allow(dead_code)]
-    pub fn record_move(&mut self, from: Position, to: Position, promotion_piece: Option<PieceType>) {
-        let move_count = self.piece_move_history.entry(from).or_insert(0);
-        *move_count += 1;
+    /// Emit the event describing the current status, if it's anything but in progress
+    fn emit_status(&self) {
+        if self.observer.is_none() {
+            return;
+        }
         
-        let game_move = Move {
-            from,
-            to,
-            promotion_piece,
+        let event = match self.status {
+            GameStatus::InProgress => return,
+            GameStatus::Check { player } => GameEvent::Check(player),
+            GameStatus::Checkmate { winner } => GameEvent::Checkmate(winner),
+            GameStatus::Stalemate => GameEvent::Draw(DrawReason::Stalemate),
+            GameStatus::Draw(reason) => GameEvent::Draw(reason),
+            GameStatus::Resigned { winner } => GameEvent::Resigned(winner),
         };
+        self.emit(event);
+    }
+    
+    /// Replay `moves` from the initial position, returning the index of the first illegal one
+    /// along with why it was refused
+    pub fn from_moves(moves: &[Move]) -> Result<Self, (usize, MoveError)> {
+        let mut game = GameState::new();
+        for (index, game_move) in moves.iter().enumerate() {
+            game.make_move(game_move).map_err(|err| (index, err))?;
+        }
+        Ok(game)
+    }
+    
+    /// Start a new game from the initial position, keeping the observer, clock and settings
+    pub fn reset(&mut self) {
+        let observer = self.observer.take();
+        let clock = self.clock.take();
+        let default_promotion = self.default_promotion;
         
-        // Record last move for en passant detection
-        self.last_move = Some(game_move.clone());
+        *self = GameState::new();
+        self.default_promotion = default_promotion;
+        if let Some(clock) = clock {
+            self.attach_clock(clock);
+        }
+        self.observer = observer;
         
-        // Record move in history for threefold repetition detection
-        self.move_history.push(game_move);
+        self.emit(GameEvent::GameReset);
     }
     
-    /// Check if a piece has moved (for castling)
-    #[allow(dead_code)]
-    pub fn has_piece_moved(&self, pos: Position) -> bool {
-        self.piece_move_history.get(&pos).copied().unwrap_or(0) > 0
+    /// Attach a clock so that the time taken for each move is recorded, starting now
+    pub fn attach_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.last_move_time = Some(clock.now());
+        self.clock = Some(clock);
     }
     
-    /// Get the last move (for en passant)
-    #[allow(dead_code)]
-    pub fn get_last_move(&self) -> Option<&Move> {
-        self.last_move.as_ref()
+    /// Get the time taken for each move played since a clock was attached
+    pub fn move_times(&self) -> &[Duration] {
+        &self.move_times
     }
     
-    /// Record a pawn promotion
-    #[allow(dead_code)]
-    pub fn record_promotion(&mut self, pos: Position) {
-        self.promoted_pawns.insert(pos);
+    /// Check that a move is legal for the current player exactly as given
+    ///
+    /// A pawn move to the last rank must name its promotion piece, and other moves must not.
+    pub fn validate_move(&self, game_move: &Move) -> Result<(), MoveError> {
+        let piece = match self.board.get_piece(&game_move.from) {
+            Some(p) => *p,
+            None => return Err(MoveError::NoPiece),
+        };
+        
+        if piece.color != self.current_player {
+            return Err(MoveError::WrongTurn);
+        }
+        
+        if self.board.is_castling_move(&game_move.from, &game_move.to) {
+            return match game_move.promotion_piece {
+                None if self.castling_moves().contains(game_move) => Ok(()),
+                _ => Err(MoveError::IllegalMove),
+            };
+        }
+        
+        if self.is_en_passant(game_move) {
+            return match game_move.promotion_piece {
+                None if self.en_passant_moves().contains(game_move) => Ok(()),
+                _ => Err(MoveError::IllegalMove),
+            };
+        }
+        
+        if !self.board.is_valid_move_for(&game_move.from, &game_move.to, self.current_player) {
+            return Err(MoveError::IllegalMove);
+        }
+        
+        let requires_promotion = self.board.requires_promotion(&game_move.from, &game_move.to);
+        match game_move.promotion_piece {
+            None if requires_promotion => Err(MoveError::PromotionRequired),
+            Some(_) if !requires_promotion => Err(MoveError::IllegalMove),
+            Some(PieceType::King | PieceType::Pawn) => Err(MoveError::IllegalMove),
+            _ => Ok(()),
+        }
     }
     
-    /// Check if a pawn has been promoted
-    #[allow(dead_code)]
-    pub fn is_promoted_pawn(&self, pos: Position) -> bool {
-        self.promoted_pawns.contains(&pos)
+    /// Make a move for the current player, then switch turns and update the status
+    ///
+    /// A pawn reaching the last rank without a promotion piece promotes to `default_promotion`.
+    /// Once the game is over every move is refused with `MoveError::GameOver`.
+    pub fn make_move(&mut self, game_move: &Move) -> Result<(), MoveError> {
+        if self.is_game_over() {
+            return Err(MoveError::GameOver);
+        }
+        
+        let mut game_move = *game_move;
+        if game_move.promotion_piece.is_none() && self.board.requires_promotion(&game_move.from, &game_move.to) {
+            game_move.promotion_piece = Some(self.default_promotion);
+        }
+        self.validate_move(&game_move)?;
+        
+        let piece = *self.board.get_piece(&game_move.from).ok_or(MoveError::NoPiece)?;
+        let promotion_piece = game_move.promotion_piece;
+        let is_en_passant = self.is_en_passant(&game_move);
+        let captured = self.captured_piece(&game_move);
+        
+        self.undo_stack.push(self.snapshot());
+        self.position_keys.push(self.position_key());
+        
+        self.castling_rights.clear_for_square(game_move.from);
+        self.castling_rights.clear_for_square(game_move.to);
+        
+        let is_double_step = piece.piece_type == PieceType::Pawn
+            && (game_move.to.rank as i8 - game_move.from.rank as i8).abs() == 2;
+        self.en_passant_target = if is_double_step {
+            Some(Position::new_unchecked(game_move.from.file, (game_move.from.rank + game_move.to.rank) / 2))
+        } else {
+            None
+        };
+        
+        if piece.piece_type == PieceType::Pawn || captured.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if piece.color == Color::Black {
+            self.fullmove_number += 1;
+        }
+        
+        if let Some(captured) = captured {
+            self.captured_pieces.push(captured);
+        }
+        
+        if self.board.is_castling_move(&game_move.from, &game_move.to) {
+            self.board.castle(&game_move.from, &game_move.to);
+        } else if is_en_passant {
+            self.board.capture_en_passant(&game_move.from, &game_move.to);
+        } else {
+            self.board.make_move(&game_move.from, &game_move.to);
+        }
+        if let Some(promote_to) = promotion_piece {
+            self.board.set_piece(game_move.to, Piece::new(promote_to, piece.color));
+            self.record_promotion(game_move.to);
+        }
+        self.record_move(game_move.from, game_move.to, promotion_piece);
+        self.switch_turn();
+        self.refresh_status();
+        self.evaluation_cache = None;
+        
+        if let Some(clock) = &self.clock {
+            let now = clock.now();
+            let since = self.last_move_time.unwrap_or(now);
+            self.move_times.push(now.saturating_sub(since));
+            self.last_move_time = Some(now);
+        }
+        
+        self.emit(GameEvent::MoveMade(game_move));
+        self.emit_status();
+        
+        Ok(())
     }
     
-    /// Switch the current player
-    pub fn switch_turn(&mut self) {
-        self.current_player = match self.current_player {
-            Color::White => Color::Black,
-            Color::Black => Color::White,
+    /// Undo the last move made through `make_move`, returning it
+    pub fn undo(&mut self) -> Option<Move> {
+        let snapshot = self.undo_stack.pop()?;
+        let undone = self.move_history.pop();
+        self.position_keys.pop();
+        
+        // Replaying the undone move on the restored board must give the board being replaced
+        #[cfg(debug_assertions)]
+        if let Some(undone) = undone {
+            Board::assert_move_invariants(&snapshot.board, &self.board, &undone.from, &undone.to);
+        }
+        
+        self.restore(snapshot);
+        
+        if let Some(undone) = undone {
+            self.emit(GameEvent::MoveUndone(undone));
+        }
+        
+        undone
+    }
+    
+    /// Put back the position saved in `snapshot`
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.board = snapshot.board;
+        self.current_player = snapshot.current_player;
+        self.status = snapshot.status;
+        self.piece_move_history = snapshot.piece_move_history;
+        self.last_move = snapshot.last_move;
+        self.castling_rights = snapshot.castling_rights;
+        self.en_passant_target = snapshot.en_passant_target;
+        self.promoted_pawns = snapshot.promoted_pawns;
+        self.captured_pieces = snapshot.captured_pieces;
+        self.move_times = snapshot.move_times;
+        self.last_move_time = snapshot.last_move_time;
+        self.halfmove_clock = snapshot.halfmove_clock;
+        self.fullmove_number = snapshot.fullmove_number;
+        self.evaluation_cache = None;
+    }
+    
+    /// Check that the board is what replaying the recorded moves from the starting position
+    /// gives, catching pieces set or removed behind the game's back
+    pub fn verify_consistency(&self) -> Result<(), ConsistencyError> {
+        let mut replay = self.initial_position();
+        
+        for (index, game_move) in self.move_history.iter().enumerate() {
+            replay.make_move(game_move)
+                .map_err(|error| ConsistencyError::IllegalMove { index, error })?;
+        }
+        
+        let squares = self.board.diff(&replay.board);
+        if !squares.is_empty() {
+            return Err(ConsistencyError::BoardMismatch(squares));
+        }
+        if self.current_player != replay.current_player {
+            return Err(ConsistencyError::WrongPlayer);
+        }
+        Ok(())
+    }
+    
+    /// A fresh game at the position this one was started or set up from, with the same
+    /// settings but no observer or clock
+    fn initial_position(&self) -> GameState {
+        let mut initial = GameState::from_fen(&self.start_fen).expect("the starting FEN is written by to_fen");
+        initial.default_promotion = self.default_promotion;
+        initial
+    }
+    
+    /// The move `undo` would take back, without undoing it
+    ///
+    /// Unlike `get_last_move`, this is `None` for moves that can't be undone, such as those
+    /// only recorded with `record_move`.
+    pub fn peek_undo(&self) -> Option<&Move> {
+        if self.undo_stack.is_empty() {
+            return None;
+        }
+        self.move_history.last()
+    }
+    
+    /// Preview the result of a move on a copy of the game, leaving this state untouched
+    pub fn preview_move(&self, game_move: &Move) -> Result<MovePreview, MoveError> {
+        let captured_piece = self.captured_piece(game_move);
+        
+        let mut preview = self.clone();
+        preview.clear_observer();
+        preview.make_move(game_move)?;
+        
+        Ok(MovePreview {
+            gives_check: preview.board.is_king_in_check(preview.current_player),
+            is_capture: captured_piece.is_some(),
+            captured_piece,
+            status: preview.status,
+        })
+    }
+    
+    /// Get the castling rights still available, taking into account that the king and rook
+    /// must be on their starting squares
+    pub fn castling_rights(&self) -> CastlingRights {
+        let on_square = |file, rank, piece_type, color| {
+            self.board.get_piece(&Position::new_unchecked(file, rank)) == Some(&Piece::new(piece_type, color))
         };
+        let white_king = on_square(4, 0, PieceType::King, Color::White);
+        let black_king = on_square(4, 7, PieceType::King, Color::Black);
+        let rights = self.castling_rights;
+        
+        CastlingRights {
+            white_kingside: rights.white_kingside && white_king && on_square(7, 0, PieceType::Rook, Color::White),
+            white_queenside: rights.white_queenside && white_king && on_square(0, 0, PieceType::Rook, Color::White),
+            black_kingside: rights.black_kingside && black_king && on_square(7, 7, PieceType::Rook, Color::Black),
+            black_queenside: rights.black_queenside && black_king && on_square(0, 7, PieceType::Rook, Color::Black),
+        }
     }
     
-    /// Update the game status
-    #[allow(dead_code)]
-    pub fn update_status(&mut self, new_status: GameStatus) {
-        self.status = new_status;
+    /// Generate the castling moves available to the current player, written as king moves
+    /// of two squares
+    pub fn castling_moves(&self) -> Vec<Move> {
+        let rights = self.castling_rights();
+        let (kingside, queenside, home_rank) = match self.current_player {
+            Color::White => (rights.white_kingside, rights.white_queenside, 0),
+            Color::Black => (rights.black_kingside, rights.black_queenside, 7),
+        };
+        
+        let from = Position::new_unchecked(4, home_rank);
+        [(kingside, 6), (queenside, 2)].into_iter()
+            .filter(|&(allowed, _)| allowed)
+            .map(|(_, file)| Move { from, to: Position::new_unchecked(file, home_rank), promotion_piece: None })
+            .filter(|game_move| self.board.can_castle(&game_move.from, &game_move.to))
+            .collect()
     }
-
-    /// Get a Unicode character representation of a piece
-    pub fn get_piece_symbol(piece: &Piece) -> &'static str {
-        match (piece.piece_type, piece.color) {
-            (PieceType::King, Color::White) => "♔",
-            (PieceType::Queen, Color::White) => "♕",
-            (PieceType::Rook, Color::White) => "♖",
-            (PieceType::Bishop, Color::White) => "♗",
-            (PieceType::Knight, Color::White) => "♘",
-            (PieceType::Pawn, Color::White) => "♙",
-            (PieceType::King, Color::Black) => "♚",
-            (PieceType::Queen, Color::Black) => "♛",
-            (PieceType::Rook, Color::Black) => "♜",
-            (PieceType::Bishop, Color::Black) => "♝",
-            (PieceType::Knight, Color::Black) => "♞",
-            (PieceType::Pawn, Color::Black) => "♟",
+    
+    /// Generate the en passant captures available to the current player
+    pub fn en_passant_moves(&self) -> Vec<Move> {
+        let target = match self.en_passant_target {
+            Some(target) => target,
+            None => return Vec::new(),
+        };
+        let capturer_rank = if self.current_player == Color::White { 4 } else { 3 };
+        let capturer = Piece::new(PieceType::Pawn, self.current_player);
+        
+        [-1, 1].iter()
+            .filter_map(|&step| Position::try_new(target.file as i8 + step, capturer_rank))
+            .map(|from| Move { from, to: target, promotion_piece: None })
+            .filter(|game_move| self.board.get_piece(&game_move.from) == Some(&capturer))
+            .filter(|game_move| {
+                // Removing both pawns from the rank can expose the king
+                let mut after = self.board.clone();
+                after.capture_en_passant(&game_move.from, &game_move.to) && !after.is_king_in_check(self.current_player)
+            })
+            .collect()
+    }
+    
+    /// Check if a move is a pawn capturing onto the en passant target square
+    fn is_en_passant(&self, game_move: &Move) -> bool {
+        self.en_passant_target == Some(game_move.to)
+            && game_move.from.file != game_move.to.file
+            && self.board.get_piece(&game_move.from).is_some_and(|piece| piece.piece_type == PieceType::Pawn)
+    }
+    
+    /// The piece a move of the current position captures, including a pawn taken en passant
+    fn captured_piece(&self, game_move: &Move) -> Option<Piece> {
+        if self.is_en_passant(game_move) {
+            self.board.get_piece(&Position::new_unchecked(game_move.to.file, game_move.from.rank)).copied()
+        } else {
+            self.board.get_piece(&game_move.to).copied()
+        }
+    }
+    
+    /// Check if the current player may move the piece on `from` to `to`, taking turns,
+    /// check, castling rights and en passant into account
+    ///
+    /// Unlike `validate_move` a pawn reaching the last rank needs no promotion piece, so this
+    /// suits move highlighting and two-click input.
+    pub fn is_legal(&self, from: Position, to: Position) -> bool {
+        let game_move = Move { from, to, promotion_piece: None };
+        matches!(self.validate_move(&game_move), Ok(()) | Err(MoveError::PromotionRequired))
+    }
+    
+    /// Check if moving the piece on `from` to `to` is a legal pawn move reaching the last rank,
+    /// so the GUI knows to ask for the promotion piece before completing it
+    pub fn is_promotion_move(&self, from: Position, to: Position) -> bool {
+        self.board.requires_promotion(&from, &to) && self.is_legal(from, to)
+    }
+    
+    /// The squares where the piece on `from` can legally move and promote, empty unless it's
+    /// a pawn of the current player about to reach the last rank
+    pub fn promotion_squares(&self, from: Position) -> Vec<Position> {
+        self.board.get_valid_moves(&from).into_iter()
+            .filter(|&to| self.is_promotion_move(from, to))
+            .collect()
+    }
+    
+    /// Classify a move of the current position by what it does on the board
+    pub fn move_kind(&self, game_move: &Move) -> MoveKind {
+        if self.board.is_castling_move(&game_move.from, &game_move.to) {
+            MoveKind::Castle
+        } else if self.is_en_passant(game_move) {
+            MoveKind::EnPassant
+        } else if self.board.requires_promotion(&game_move.from, &game_move.to) {
+            MoveKind::Promotion
+        } else if self.board.get_piece(&game_move.to).is_some() {
+            MoveKind::Capture
+        } else {
+            MoveKind::Normal
+        }
+    }
+    
+    /// Get the en passant target square, only when a pawn of the player to move could
+    /// actually capture onto it
+    pub fn en_passant_square(&self) -> Option<Position> {
+        let target = self.en_passant_target?;
+        (!self.en_passant_moves().is_empty()).then_some(target)
+    }
+    
+    /// The current player's pieces pinned to their king, each paired with the enemy piece pinning it
+    pub fn pinned_pieces(&self) -> Vec<(Position, Position)> {
+        self.board.pinned_pieces(self.current_player)
+    }
+    
+    /// Check if the player to move is in check
+    pub fn is_in_check(&self) -> bool {
+        self.board.is_king_in_check(self.current_player)
+    }
+    
+    /// Check if the player who just moved is in check, which only happens in positions
+    /// that were set up by hand
+    pub fn is_opponent_in_check(&self) -> bool {
+        self.board.is_king_in_check(self.current_player.opposite())
+    }
+    
+    /// Classify the check on the player to move, using the last move to tell direct
+    /// checks from discovered ones
+    pub fn check_type(&self) -> Option<CheckType> {
+        let last_move = self.last_move?;
+        self.board.check_type(self.current_player, &last_move.to)
+    }
+    
+    /// Set up a game from a FEN string
+    ///
+    /// The placement, side to move, castling and en passant fields must be present; the
+    /// halfmove clock and fullmove number default to 0 and 1. Returns `None` if a field
+    /// is malformed.
+    pub fn from_fen(fen: &str) -> Option<Self> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() < 4 {
+            return None;
+        }
+        
+        let mut game = GameState::new();
+        game.board = Board::from_fen(fields[0]).ok()?;
+        game.current_player = match fields[1] {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => return None,
+        };
+        game.castling_rights = CastlingRights::from_fen(fields[2])?;
+        game.en_passant_target = match fields[3] {
+            "-" => None,
+            square => Some(Position::from_notation(square)?),
+        };
+        game.halfmove_clock = match fields.get(4) {
+            Some(field) => field.parse().ok()?,
+            None => 0,
+        };
+        game.fullmove_number = match fields.get(5) {
+            Some(field) => field.parse().ok().filter(|&number| number > 0)?,
+            None => 1,
+        };
+        game.refresh_status();
+        game.start_fen = game.to_fen();
+        
+        Some(game)
+    }
+    
+    /// The FEN string of the current position, with en passant written only when a capture
+    /// is possible, as in `position_key`
+    pub fn to_fen(&self) -> String {
+        format!("{} {} {}", self.position_key(), self.halfmove_clock, self.fullmove_number)
+    }
+    
+    /// Key identifying the position for repetition: piece placement, side to move,
+    /// castling rights and en passant availability, in FEN field order
+    pub fn position_key(&self) -> String {
+        let side = match self.current_player {
+            Color::White => "w",
+            Color::Black => "b",
+        };
+        let en_passant = self.en_passant_square().map_or("-".to_string(), |pos| pos.to_notation());
+        
+        format!("{} {} {} {}", self.board.to_fen(), side, self.castling_rights().to_fen(), en_passant)
+    }
+    
+    /// Zobrist hash of the position: pieces, side to move, castling rights and en passant
+    /// availability, as for `position_key`
+    ///
+    /// The piece part is maintained incrementally by the board as moves are made and undone.
+    pub fn hash(&self) -> u64 {
+        self.board.piece_hash() ^ self.state_hash()
+    }
+    
+    /// Compute the same hash as `hash` from scratch
+    pub fn zobrist_hash(&self) -> u64 {
+        self.board.zobrist_hash(Color::White) ^ self.state_hash()
+    }
+    
+    /// Hash keys for everything but the pieces
+    fn state_hash(&self) -> u64 {
+        let rights = self.castling_rights();
+        let castling = [rights.white_kingside, rights.white_queenside, rights.black_kingside, rights.black_queenside]
+            .iter()
+            .enumerate()
+            .filter(|(_, &has_right)| has_right)
+            .fold(0, |hash, (index, _)| hash ^ zobrist::castling_key(index));
+        let en_passant = self.en_passant_square().map_or(0, |pos| zobrist::en_passant_key(pos.file));
+        
+        zobrist::side_to_move_key(self.current_player) ^ castling ^ en_passant
+    }
+    
+    /// Count how many times the current position has occurred, including now
+    pub fn repetition_count(&self) -> usize {
+        let key = self.position_key();
+        1 + self.position_keys.iter().filter(|previous| **previous == key).count()
+    }
+    
+    /// Check if the current position has occurred at least three times
+    ///
+    /// As the rules have it, a threefold repetition only lets the player to move claim a draw
+    /// with `claim_draw`; the game is drawn without a claim on the fifth occurrence.
+    pub fn has_threefold_repetition(&self) -> bool {
+        self.repetition_count() >= 3
+    }
+    
+    /// Check if fifty moves by each side have been played without a pawn move or capture,
+    /// so a draw may be claimed
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.halfmove_clock >= 100
+    }
+    
+    /// Get the result if the game is over
+    ///
+    /// Draws that must be claimed, such as threefold repetition, don't end the game until
+    /// claimed with `claim_draw`; see `claimable_draws`.
+    pub fn get_game_result(&self) -> Option<GameResult> {
+        match self.status {
+            GameStatus::Checkmate { winner } => Some(GameResult::Checkmate { winner }),
+            GameStatus::Stalemate => Some(GameResult::Draw(DrawReason::Stalemate)),
+            GameStatus::Resigned { winner } => Some(GameResult::Resignation { winner }),
+            GameStatus::Draw(reason) => Some(GameResult::Draw(reason)),
+            _ => self.automatic_draw().map(GameResult::Draw),
+        }
+    }
+    
+    /// End the game with `color` resigning, unless it's already over
+    pub fn resign(&mut self, color: Color) {
+        if self.is_game_over() {
+            return;
+        }
+        self.status = GameStatus::Resigned { winner: color.opposite() };
+        self.emit_status();
+    }
+    
+    /// End the game in a draw both players agreed to, unless it's already over
+    pub fn agree_draw(&mut self) {
+        if self.is_game_over() {
+            return;
+        }
+        self.status = GameStatus::Draw(DrawReason::Agreement);
+        self.emit_status();
+    }
+    
+    /// Check if the game has ended by checkmate, resignation, an agreed draw or a draw that
+    /// needs no claim
+    pub fn is_game_over(&self) -> bool {
+        self.get_game_result().is_some()
+    }
+    
+    /// List the draws the player to move may claim while the game continues
+    pub fn claimable_draws(&self) -> Vec<DrawReason> {
+        if self.get_game_result().is_some() {
+            return Vec::new();
+        }
+        
+        let mut claims = Vec::new();
+        if self.has_threefold_repetition() {
+            claims.push(DrawReason::ThreefoldRepetition);
+        }
+        if self.is_fifty_move_draw() {
+            claims.push(DrawReason::FiftyMoveRule);
+        }
+        claims
+    }
+    
+    /// End the game with the first draw the player to move may claim, returning it, or
+    /// leave the game going if there is none
+    pub fn claim_draw(&mut self) -> Option<DrawReason> {
+        let reason = *self.claimable_draws().first()?;
+        self.status = GameStatus::Draw(reason);
+        self.emit_status();
+        Some(reason)
+    }
+    
+    /// Check if the player to move may claim a draw in the current position
+    pub fn can_claim_draw(&self) -> bool {
+        !self.claimable_draws().is_empty()
+    }
+    
+    /// Check if the player to move may claim a draw by announcing `game_move`, because
+    /// playing it would repeat a position for the third time or reach the fifty-move limit
+    pub fn can_claim_draw_with(&self, game_move: &Move) -> bool {
+        let mut after = self.clone();
+        after.clear_observer();
+        if after.make_move(game_move).is_err() || after.get_game_result().is_some() {
+            return false;
+        }
+        
+        after.has_threefold_repetition() || after.is_fifty_move_draw()
+    }
+    
+    /// The draw that ends the game without a claim, if any
+    fn automatic_draw(&self) -> Option<DrawReason> {
+        if self.board.is_dead_position() {
+            Some(DrawReason::DeadPosition)
+        } else if self.repetition_count() >= 5 {
+            Some(DrawReason::FivefoldRepetition)
+        } else if self.halfmove_clock >= 150 {
+            Some(DrawReason::SeventyFiveMoveRule)
+        } else {
+            None
+        }
+    }
+    
+    /// Compress the game into its starting position and move list
+    pub fn to_compact(&self) -> CompactGame {
+        CompactGame {
+            initial_fen: self.start_fen.clone(),
+            moves: self.move_history.clone(),
+        }
+    }
+    
+    /// Rebuild a game by replaying the moves of a `CompactGame` from its starting position
+    pub fn from_compact(compact: &CompactGame) -> Result<Self, CompactError> {
+        let mut game = GameState::from_fen(&compact.initial_fen).ok_or(CompactError::InvalidFen)?;
+        
+        for (index, game_move) in compact.moves.iter().enumerate() {
+            game.make_move(game_move)
+                .map_err(|error| CompactError::IllegalMove { index, error })?;
+        }
+        
+        Ok(game)
+    }
+    
+    /// Serialize the game to JSON for saving, as its `CompactGame`
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+    
+    /// Load a game saved with `to_json`
+    ///
+    /// The moves are replayed from the starting position, so the status, the undo history and
+    /// caches such as the board's hash are rebuilt rather than trusted.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+    
+    /// Find the legal move described by `san` in standard algebraic notation, e.g.
+    /// "e4", "Nbd7", "exd5", "e8=Q" or "O-O"
+    ///
+    /// Check and annotation suffixes such as "+", "#" and "!?" are ignored.
+    pub fn parse_san(&self, san: &str) -> Result<Move, SanError> {
+        let home_rank = if self.current_player == Color::White { 0 } else { 7 };
+        
+        let (piece_type, from_file, from_rank, to, promotion_piece) = match SanParts::parse(san)? {
+            // Castling is written as a king move to its destination
+            SanParts::Castle { kingside } => {
+                let from = Position::new_unchecked(4, home_rank);
+                let to = Position::new_unchecked(if kingside { 6 } else { 2 }, home_rank);
+                let game_move = Move { from, to, promotion_piece: None };
+                if !self.castling_moves().contains(&game_move) {
+                    return Err(SanError::NoMatchingMove);
+                }
+                return Ok(game_move);
+            }
+            SanParts::Move { piece_type, from_file, from_rank, to, promotion_piece } => {
+                (piece_type, from_file, from_rank, to, promotion_piece)
+            }
+        };
+        
+        let mover = Piece::new(piece_type, self.current_player);
+        let mut candidates = Vec::new();
+        for rank in 0..8 {
+            for file in 0..8 {
+                if from_file.is_some_and(|f| f != file) || from_rank.is_some_and(|r| r != rank) {
+                    continue;
+                }
+                let from = Position::new_unchecked(file, rank);
+                if self.board.get_piece(&from) == Some(&mover) && self.is_legal(from, to) {
+                    candidates.push(from);
+                }
+            }
+        }
+        
+        SanParts::resolve(&candidates, to, promotion_piece)
+    }
+    
+    /// Write a legal move in standard algebraic notation, with the minimal disambiguation
+    /// and a "+" or "#" suffix for check and mate
+    pub fn to_san(&self, game_move: &Move) -> Result<String, MoveError> {
+        self.to_san_with_style(game_move, SanStyle::default())
+    }
+    
+    /// Write a legal move in standard algebraic notation, with castling and check markers
+    /// as `style` asks
+    pub fn to_san_with_style(&self, game_move: &Move, style: SanStyle) -> Result<String, MoveError> {
+        let preview = self.preview_move(game_move)?;
+        let piece = *self.board.get_piece(&game_move.from).ok_or(MoveError::NoPiece)?;
+        let (from, to) = (game_move.from, game_move.to);
+        
+        let mut san = String::new();
+        if piece.piece_type == PieceType::King && (to.file as i8 - from.file as i8).abs() == 2 {
+            let castle = match (to.file > from.file, style.zero_castling) {
+                (true, false) => "O-O",
+                (false, false) => "O-O-O",
+                (true, true) => "0-0",
+                (false, true) => "0-0-0",
+            };
+            san.push_str(castle);
+        } else if piece.piece_type == PieceType::Pawn {
+            if preview.is_capture {
+                san.push((b'a' + from.file) as char);
+                san.push('x');
+            }
+            san.push_str(&to.to_notation());
+            
+            if self.board.requires_promotion(&from, &to) {
+                let promote_to = game_move.promotion_piece.unwrap_or(self.default_promotion);
+                san.push('=');
+                san.push(Piece::new(promote_to, Color::White).to_fen_char());
+            }
+        } else {
+            san.push(Piece::new(piece.piece_type, Color::White).to_fen_char());
+            
+            // Name the file, rank or both when another such piece could also move there
+            let rivals: Vec<Position> = self.generate_legal_moves().iter()
+                .filter(|m| m.to == to && m.from != from && self.board.get_piece(&m.from) == Some(&piece))
+                .map(|m| m.from)
+                .collect();
+            if !rivals.is_empty() {
+                let file_clash = rivals.iter().any(|pos| pos.file == from.file);
+                let rank_clash = rivals.iter().any(|pos| pos.rank == from.rank);
+                if !file_clash {
+                    san.push((b'a' + from.file) as char);
+                } else if !rank_clash {
+                    san.push((b'1' + from.rank) as char);
+                } else {
+                    san.push_str(&from.to_notation());
+                }
+            }
+            
+            if preview.is_capture {
+                san.push('x');
+            }
+            san.push_str(&to.to_notation());
+        }
+        
+        if !style.check_markers {
+            return Ok(san);
+        }
+        if matches!(preview.status, GameStatus::Checkmate { .. }) {
+            san.push('#');
+        } else if preview.gives_check {
+            san.push('+');
+        }
+        Ok(san)
+    }
+    
+    /// Write the game as PGN movetext, e.g. "1. e4 e5 2. Nf3 *", ending with the result
+    ///
+    /// Moves are numbered from the starting position, and written up to the first one that
+    /// can't be replayed from it.
+    pub fn to_pgn(&self) -> String {
+        self.to_pgn_with_style(SanStyle::default())
+    }
+    
+    /// Write the game as PGN movetext like `to_pgn`, with castling and check markers as
+    /// `style` asks
+    pub fn to_pgn_with_style(&self, style: SanStyle) -> String {
+        let mut replay = self.initial_position();
+        
+        let mut tokens = Vec::new();
+        for game_move in &self.move_history {
+            if replay.current_player == Color::White {
+                tokens.push(format!("{}.", replay.fullmove_number));
+            } else if tokens.is_empty() {
+                tokens.push(format!("{}...", replay.fullmove_number));
+            }
+            
+            // The history only holds moves that were legal when they were made
+            let san = match replay.to_san_with_style(game_move, style) {
+                Ok(san) => san,
+                Err(_) => break,
+            };
+            if replay.make_move(game_move).is_err() {
+                break;
+            }
+            tokens.push(san);
+        }
+        
+        let result = match self.get_game_result() {
+            Some(GameResult::Checkmate { winner }) | Some(GameResult::Resignation { winner }) => {
+                if winner == Color::White { "1-0" } else { "0-1" }
+            }
+            Some(GameResult::Draw(_)) => "1/2-1/2",
+            None => "*",
+        };
+        tokens.push(result.to_string());
+        tokens.join(" ")
+    }
+    
+    /// Play a move given in standard algebraic notation, returning the move that was made
+    pub fn apply_san(&mut self, san: &str) -> Result<Move, SanError> {
+        let game_move = self.parse_san(san)?;
+        self.make_move(&game_move).map_err(SanError::IllegalMove)?;
+        
+        // The recorded move includes any default promotion piece
+        Ok(self.last_move.unwrap_or(game_move))
+    }
+    
+    /// Get the cached evaluation of the current position, if one has been computed
+    pub fn cached_evaluation(&self) -> Option<f32> {
+        self.evaluation_cache
+    }
+    
+    /// Evaluate the current position, reusing the cached value when the position hasn't changed
+    pub fn evaluate<F>(&mut self, evaluator: F) -> f32
+    where
+        F: FnOnce(&Board) -> f32,
+    {
+        if let Some(value) = self.evaluation_cache {
+            return value;
+        }
+        
+        let value = evaluator(&self.board);
+        self.evaluation_cache = Some(value);
+        value
+    }
+    
+    /// Capture the state needed to undo a move
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            board: self.board.clone(),
+            current_player: self.current_player,
+            status: self.status.clone(),
+            piece_move_history: self.piece_move_history.clone(),
+            last_move: self.last_move,
+            castling_rights: self.castling_rights,
+            en_passant_target: self.en_passant_target,
+            promoted_pawns: self.promoted_pawns.clone(),
+            captured_pieces: self.captured_pieces.clone(),
+            move_times: self.move_times.clone(),
+            last_move_time: self.last_move_time,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+        }
+    }
+    
+    /// Recompute the status from the board for the player about to move
+    pub fn refresh_status(&mut self) {
+        let player = self.current_player;
+        let in_check = self.board.is_king_in_check(player);
+        let has_moves = self.legal_move_count() > 0;
+        let automatic_draw = self.automatic_draw();
+        
+        self.status = match (automatic_draw, in_check, has_moves) {
+            (Some(reason), _, true) => GameStatus::Draw(reason),
+            (_, true, true) => GameStatus::Check { player },
+            (_, true, false) => GameStatus::Checkmate { winner: player.opposite() },
+            (_, false, true) => GameStatus::InProgress,
+            (_, false, false) => GameStatus::Stalemate,
+        };
+    }
+    
+    /// Generate every legal move for the current player, with one move per promotion piece,
+    /// castling as a two-square king move and en passant captures
+    pub fn generate_legal_moves(&self) -> Vec<Move> {
+        let last_rank = if self.current_player == Color::White { 7 } else { 0 };
+        let mut moves = Vec::new();
+        
+        for rank in 0..8 {
+            for file in 0..8 {
+                let from = Position::new_unchecked(file, rank);
+                let piece = match self.board.get_piece(&from) {
+                    Some(piece) if piece.color == self.current_player => *piece,
+                    _ => continue,
+                };
+                
+                for to in self.board.get_valid_moves(&from) {
+                    if piece.piece_type == PieceType::Pawn && to.rank == last_rank {
+                        for promote_to in [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight] {
+                            moves.push(Move { from, to, promotion_piece: Some(promote_to) });
+                        }
+                    } else {
+                        moves.push(Move { from, to, promotion_piece: None });
+                    }
+                }
+            }
+        }
+        
+        moves.extend(self.castling_moves());
+        moves.extend(self.en_passant_moves());
+        moves
+    }
+    
+    /// Get the legal moves of `color`'s pieces of one type, e.g. every knight move for a
+    /// hint, with one move per promotion piece
+    ///
+    /// Castling and en passant depend on the game's history, so they're only included when
+    /// `color` is the side to move.
+    pub fn legal_moves_of_type(&self, color: Color, piece_type: PieceType) -> Vec<Move> {
+        let mover = Piece::new(piece_type, color);
+        let last_rank = if color == Color::White { 7 } else { 0 };
+        let mut moves = Vec::new();
+        
+        for (from, _) in self.board.iter_pieces().filter(|(_, piece)| **piece == mover) {
+            for to in self.board.get_valid_moves(&from) {
+                if piece_type == PieceType::Pawn && to.rank == last_rank {
+                    for promote_to in [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight] {
+                        moves.push(Move { from, to, promotion_piece: Some(promote_to) });
+                    }
+                } else {
+                    moves.push(Move { from, to, promotion_piece: None });
+                }
+            }
+        }
+        
+        if self.current_player == color {
+            match piece_type {
+                PieceType::King => moves.extend(self.castling_moves()),
+                PieceType::Pawn => moves.extend(self.en_passant_moves()),
+                _ => {}
+            }
+        }
+        moves
+    }
+    
+    /// Map each of the current player's pieces that can move to its legal destinations,
+    /// listing a promotion square once whatever the promotion piece
+    pub fn legal_moves_grouped(&self) -> HashMap<Position, Vec<Position>> {
+        let mut grouped: HashMap<Position, Vec<Position>> = HashMap::new();
+        for game_move in self.generate_legal_moves() {
+            let destinations = grouped.entry(game_move.from).or_default();
+            if !destinations.contains(&game_move.to) {
+                destinations.push(game_move.to);
+            }
+        }
+        grouped
+    }
+    
+    /// Count the leaf positions reached after `depth` plies of legal moves
+    pub fn perft(&mut self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        
+        let moves = self.generate_legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        
+        let mut nodes = 0;
+        for game_move in moves {
+            if self.make_move(&game_move).is_ok() {
+                nodes += self.perft(depth - 1);
+                self.undo();
+            }
+        }
+        nodes
+    }
+    
+    /// Count the same leaf positions as `perft`, searching the moves from this position on
+    /// separate threads
+    ///
+    /// Only available with the `parallel` feature.
+    #[cfg(feature = "parallel")]
+    pub fn perft_parallel(&self, depth: u32) -> u64 {
+        use rayon::prelude::*;
+        
+        if depth <= 1 {
+            return self.clone().perft(depth);
+        }
+        
+        self.generate_legal_moves()
+            .into_par_iter()
+            .map(|game_move| {
+                // Each thread plays on its own copy, without notifying the observer
+                let mut game = self.clone();
+                game.clear_observer();
+                match game.make_move(&game_move) {
+                    Ok(()) => game.perft(depth - 1),
+                    Err(_) => 0,
+                }
+            })
+            .sum()
+    }
+    
+    /// Count the valid moves available to the current player
+    pub fn legal_move_count(&self) -> usize {
+        let mut count = 0;
+        for rank in 0..8 {
+            for file in 0..8 {
+                let from = Position::new_unchecked(file, rank);
+                if let Some(piece) = self.board.get_piece(&from) {
+                    if piece.color == self.current_player {
+                        count += self.board.get_valid_moves(&from).len();
+                    }
+                }
+            }
+        }
+        count + self.castling_moves().len() + self.en_passant_moves().len()
+    }
+    
+    /// Record a piece movement
+    #[allow(dead_code)]
+    pub fn record_move(&mut self, from: Position, to: Position, promotion_piece: Option<PieceType>) {
+        let move_count = self.piece_move_history.entry(from).or_insert(0);
+        *move_count += 1;
+        
+        let game_move = Move {
+            from,
+            to,
+            promotion_piece,
+        };
+        
+        // Record last move for en passant detection
+        self.last_move = Some(game_move);
+        
+        // Record move in history for threefold repetition detection
+        self.move_history.push(game_move);
+    }
+    
+    /// Check if a piece has moved (for castling)
+    #[allow(dead_code)]
+    pub fn has_piece_moved(&self, pos: Position) -> bool {
+        self.piece_move_history.get(&pos).copied().unwrap_or(0) > 0
+    }
+    
+    /// Get the last move (for en passant)
+    #[allow(dead_code)]
+    pub fn get_last_move(&self) -> Option<&Move> {
+        self.last_move.as_ref()
+    }
+    
+    /// Record a pawn promotion
+    #[allow(dead_code)]
+    pub fn record_promotion(&mut self, pos: Position) {
+        self.promoted_pawns.insert(pos);
+    }
+    
+    /// Check if a pawn has been promoted
+    #[allow(dead_code)]
+    pub fn is_promoted_pawn(&self, pos: Position) -> bool {
+        self.promoted_pawns.contains(&pos)
+    }
+    
+    /// Switch the current player
+    pub fn switch_turn(&mut self) {
+        self.current_player = match self.current_player {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        };
+    }
+    
+    /// Update the game status
+    #[allow(dead_code)]
+    pub fn update_status(&mut self, new_status: GameStatus) {
+        self.status = new_status;
+    }
+
+    /// Get a Unicode character representation of a piece
+    pub fn get_piece_symbol(piece: &Piece) -> &'static str {
+        piece.unicode_symbol()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    
+    /// A clock that only moves when told to
+    #[derive(Debug, Default)]
+    struct MockClock {
+        now: Mutex<Duration>,
+    }
+    
+    impl MockClock {
+        fn advance(&self, by: Duration) {
+            *self.now.lock().unwrap() += by;
+        }
+    }
+    
+    impl Clock for MockClock {
+        fn now(&self) -> Duration {
+            *self.now.lock().unwrap()
+        }
+    }
+    
+    fn material(board: &Board) -> f32 {
+        let mut value = 0.0;
+        for rank in 0..8 {
+            for file in 0..8 {
+                if let Some(piece) = board.get_piece(&Position::new_unchecked(file, rank)) {
+                    if piece.color == Color::White {
+                        value += 1.0;
+                    } else {
+                        value -= 1.0;
+                    }
+                }
+            }
+        }
+        value
+    }
+    
+    /// All valid moves for the current player, in board order
+    fn all_moves(game: &GameState) -> Vec<Move> {
+        let mut moves = Vec::new();
+        for rank in 0..8 {
+            for file in 0..8 {
+                let from = Position::new_unchecked(file, rank);
+                if game.board.get_piece(&from).map(|p| p.color) == Some(game.current_player) {
+                    for to in game.board.get_valid_moves(&from) {
+                        moves.push(Move { from, to, promotion_piece: None });
+                    }
+                }
+            }
+        }
+        moves
+    }
+    
+    /// Play a deterministic but varied game of up to `plies` half-moves
+    fn scripted_game(plies: usize) -> GameState {
+        let mut game = GameState::new();
+        for ply in 0..plies {
+            let moves = all_moves(&game);
+            if moves.is_empty() {
+                break;
+            }
+            game.make_move(&moves[(ply * 7 + 3) % moves.len()]).unwrap();
+        }
+        game
+    }
+    
+    #[test]
+    fn test_evaluation_cache_cleared_by_move_and_undo() {
+        let mut game = GameState::new();
+        assert_eq!(game.cached_evaluation(), None);
+        
+        assert_eq!(game.evaluate(material), 0.0);
+        assert_eq!(game.cached_evaluation(), Some(0.0));
+        
+        // A cached value is reused without calling the evaluator again
+        assert_eq!(game.evaluate(|_| panic!("evaluator should not run")), 0.0);
+        
+        let e4 = Move { from: Position::new_unchecked(4, 1), to: Position::new_unchecked(4, 3), promotion_piece: None };
+        game.make_move(&e4).unwrap();
+        assert_eq!(game.cached_evaluation(), None);
+        
+        assert_eq!(game.evaluate(|_| 1.5), 1.5);
+        assert_eq!(game.cached_evaluation(), Some(1.5));
+        
+        assert_eq!(game.undo(), Some(e4));
+        assert_eq!(game.cached_evaluation(), None);
+        
+        game.evaluate(material);
+        assert_eq!(game.cached_evaluation(), Some(0.0));
+    }
+    
+    #[test]
+    fn test_peek_undo() {
+        let mut game = GameState::new();
+        assert_eq!(game.peek_undo(), None);
+        
+        let e4 = game.apply_san("e4").unwrap();
+        assert_eq!(game.peek_undo(), Some(&e4));
+        let e5 = game.apply_san("e5").unwrap();
+        assert_eq!(game.peek_undo(), Some(&e5));
+        
+        assert_eq!(game.undo(), Some(e5));
+        assert_eq!(game.peek_undo(), Some(&e4));
+        game.undo();
+        assert_eq!(game.peek_undo(), None);
+        
+        // A move recorded without a snapshot is the last move but can't be undone
+        game.record_move(Position::new_unchecked(6, 0), Position::new_unchecked(5, 2), None);
+        assert!(game.get_last_move().is_some());
+        assert_eq!(game.peek_undo(), None);
+    }
+    
+    #[test]
+    fn test_promotion_squares() {
+        let square = |name| Position::from_notation(name).unwrap();
+        
+        // The b7 pawn can push to b8 or take on a8 and c8, but not on the blocked square
+        let game = GameState::from_fen("rnq1k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let mut squares = game.promotion_squares(square("b7"));
+        squares.sort_by_key(|pos| pos.file);
+        assert_eq!(squares, vec![square("a8"), square("c8")]);
+        
+        let open = GameState::from_fen("r1q1k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let mut squares = open.promotion_squares(square("b7"));
+        squares.sort_by_key(|pos| pos.file);
+        assert_eq!(squares, vec![square("a8"), square("b8"), square("c8")]);
+        
+        // Pieces other than pawns, and pawns far from the last rank, never promote
+        assert!(open.promotion_squares(square("e1")).is_empty());
+        let start = GameState::new();
+        assert!(start.promotion_squares(square("e2")).is_empty());
+    }
+    
+    #[test]
+    fn test_from_moves_replays_line() {
+        let line = ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6"];
+        let moves: Vec<Move> = line.iter()
+            .map(|uci| Move {
+                from: Position::from_notation(&uci[..2]).unwrap(),
+                to: Position::from_notation(&uci[2..]).unwrap(),
+                promotion_piece: None,
+            })
+            .collect();
+        
+        let game = GameState::from_moves(&moves).unwrap();
+        assert_eq!(game.move_history.len(), 6);
+        assert_eq!(game.position_key(), "r1bqkbnr/1ppp1ppp/p1n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R w KQkq -");
+    }
+    
+    #[test]
+    fn test_from_moves_reports_illegal_index() {
+        let mv = |from: &str, to: &str| Move {
+            from: Position::from_notation(from).unwrap(),
+            to: Position::from_notation(to).unwrap(),
+            promotion_piece: None,
+        };
+        // The bishop on f8 is still blocked by the pawn on e7
+        let moves = [mv("d2", "d4"), mv("d7", "d5"), mv("c1", "f4"), mv("f8", "b4"), mv("e2", "e3")];
+        
+        assert_eq!(GameState::from_moves(&moves).err(), Some((3, MoveError::IllegalMove)));
+    }
+    
+    #[test]
+    fn test_preview_capturing_check() {
+        let mut game = GameState::new();
+        game.board = Board::new();
+        game.board.set_piece(Position::new_unchecked(7, 0), Piece::new(PieceType::King, Color::White));
+        game.board.set_piece(Position::new_unchecked(4, 0), Piece::new(PieceType::Rook, Color::White));
+        game.board.set_piece(Position::new_unchecked(4, 7), Piece::new(PieceType::King, Color::Black));
+        game.board.set_piece(Position::new_unchecked(4, 4), Piece::new(PieceType::Pawn, Color::Black));
+        let board_before = game.board.clone();
+        
+        // Rook takes the pawn on e5 and checks the king on e8
+        let rxe5 = Move { from: Position::new_unchecked(4, 0), to: Position::new_unchecked(4, 4), promotion_piece: None };
+        let preview = game.preview_move(&rxe5).unwrap();
+        
+        assert!(preview.gives_check);
+        assert!(preview.is_capture);
+        assert_eq!(preview.captured_piece, Some(Piece::new(PieceType::Pawn, Color::Black)));
+        assert_eq!(preview.status, GameStatus::Check { player: Color::Black });
+        
+        // The real game is unchanged
+        assert_eq!(game.board, board_before);
+        assert_eq!(game.current_player, Color::White);
+        assert_eq!(game.status, GameStatus::InProgress);
+        assert!(game.get_last_move().is_none());
+    }
+    
+    fn promotion_game() -> GameState {
+        let mut game = GameState::new();
+        game.board = Board::new();
+        game.board.set_piece(Position::new_unchecked(4, 0), Piece::new(PieceType::King, Color::White));
+        game.board.set_piece(Position::new_unchecked(7, 7), Piece::new(PieceType::King, Color::Black));
+        game.board.set_piece(Position::new_unchecked(0, 6), Piece::new(PieceType::Pawn, Color::White));
+        game
+    }
+    
+    #[test]
+    fn test_default_promotion() {
+        let a8 = Position::new_unchecked(0, 7);
+        let push = Move { from: Position::new_unchecked(0, 6), to: a8, promotion_piece: None };
+        
+        // Queen unless configured otherwise
+        let mut game = promotion_game();
+        game.make_move(&push).unwrap();
+        assert_eq!(game.board.get_piece(&a8), Some(&Piece::new(PieceType::Queen, Color::White)));
+        assert_eq!(game.get_last_move().unwrap().promotion_piece, Some(PieceType::Queen));
+        assert!(game.is_promoted_pawn(a8));
+        
+        let mut game = promotion_game();
+        game.default_promotion = PieceType::Bishop;
+        game.make_move(&push).unwrap();
+        assert_eq!(game.board.get_piece(&a8), Some(&Piece::new(PieceType::Bishop, Color::White)));
+        
+        // An explicit choice wins over the default
+        let mut game = promotion_game();
+        game.default_promotion = PieceType::Bishop;
+        game.make_move(&Move { promotion_piece: Some(PieceType::Knight), ..push }).unwrap();
+        assert_eq!(game.board.get_piece(&a8), Some(&Piece::new(PieceType::Knight, Color::White)));
+    }
+    
+    #[test]
+    fn test_is_promotion_move() {
+        let mut game = GameState::new();
+        game.board = Board::new();
+        game.board.set_piece(Position::new_unchecked(4, 0), Piece::new(PieceType::King, Color::White));
+        game.board.set_piece(Position::new_unchecked(4, 7), Piece::new(PieceType::King, Color::Black));
+        game.board.set_piece(Position::new_unchecked(0, 6), Piece::new(PieceType::Pawn, Color::White));
+        game.board.set_piece(Position::new_unchecked(7, 1), Piece::new(PieceType::Pawn, Color::White));
+        
+        assert!(game.is_promotion_move(Position::new_unchecked(0, 6), Position::new_unchecked(0, 7)));
+        assert!(!game.is_promotion_move(Position::new_unchecked(7, 1), Position::new_unchecked(7, 2)));
+        
+        // Reaching the last rank isn't enough when the move itself is illegal
+        assert!(!game.is_promotion_move(Position::new_unchecked(0, 6), Position::new_unchecked(1, 7)));
+    }
+    
+    #[test]
+    fn test_validate_move_requires_promotion_piece() {
+        let game = promotion_game();
+        let push = Move {
+            from: Position::new_unchecked(0, 6),
+            to: Position::new_unchecked(0, 7),
+            promotion_piece: None,
+        };
+        
+        assert_eq!(game.validate_move(&push), Err(MoveError::PromotionRequired));
+        assert_eq!(game.validate_move(&Move { promotion_piece: Some(PieceType::Queen), ..push }), Ok(()));
+        assert_eq!(game.validate_move(&Move { promotion_piece: Some(PieceType::King), ..push }), Err(MoveError::IllegalMove));
+        
+        // Only pawns reaching the last rank may promote
+        let king_step = Move {
+            from: Position::new_unchecked(4, 0),
+            to: Position::new_unchecked(4, 1),
+            promotion_piece: Some(PieceType::Queen),
+        };
+        assert_eq!(game.validate_move(&king_step), Err(MoveError::IllegalMove));
+        assert_eq!(game.validate_move(&Move { promotion_piece: None, ..king_step }), Ok(()));
+    }
+    
+    #[test]
+    fn test_legal_move_count_opening() {
+        let game = GameState::new();
+        assert_eq!(game.legal_move_count(), 20);
+    }
+    
+    #[test]
+    fn test_legal_moves_grouped_opening() {
+        let grouped = GameState::new().legal_moves_grouped();
+        assert_eq!(grouped.len(), 10);
+        
+        for file in 0..8 {
+            let pawn = Position::new_unchecked(file, 1);
+            let mut destinations = grouped[&pawn].clone();
+            destinations.sort_by_key(|pos| pos.rank);
+            assert_eq!(destinations, vec![Position::new_unchecked(file, 2), Position::new_unchecked(file, 3)]);
+        }
+        for file in [1, 6] {
+            assert_eq!(grouped[&Position::new_unchecked(file, 0)].len(), 2);
+        }
+        assert_eq!(grouped.values().map(Vec::len).sum::<usize>(), 20);
+        
+        // A promotion square is listed once, not once per promotion piece
+        let promotion = GameState::from_fen("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(promotion.legal_moves_grouped()[&Position::new_unchecked(1, 6)].len(), 2);
+    }
+    
+    #[test]
+    fn test_legal_move_count_checkmate() {
+        // Back-rank mate: black king h8 boxed in by its own pawns, white rook on a8
+        let mut game = GameState::new();
+        game.board = Board::new();
+        game.board.set_piece(Position::new_unchecked(6, 0), Piece::new(PieceType::King, Color::White));
+        game.board.set_piece(Position::new_unchecked(0, 7), Piece::new(PieceType::Rook, Color::White));
+        game.board.set_piece(Position::new_unchecked(7, 7), Piece::new(PieceType::King, Color::Black));
+        game.board.set_piece(Position::new_unchecked(6, 6), Piece::new(PieceType::Pawn, Color::Black));
+        game.board.set_piece(Position::new_unchecked(7, 6), Piece::new(PieceType::Pawn, Color::Black));
+        game.current_player = Color::Black;
+        
+        assert_eq!(game.legal_move_count(), 0);
+        assert!(game.board.is_king_in_check(Color::Black));
+        
+        game.refresh_status();
+        assert_eq!(game.status, GameStatus::Checkmate { winner: Color::White });
+    }
+    
+    #[test]
+    fn test_legal_move_count_stalemate() {
+        // Black king on a8 with a white queen on b6 covering a7, b7 and b8
+        let mut game = GameState::new();
+        game.board = Board::new();
+        game.board.set_piece(Position::new_unchecked(0, 7), Piece::new(PieceType::King, Color::Black));
+        game.board.set_piece(Position::new_unchecked(1, 5), Piece::new(PieceType::Queen, Color::White));
+        game.board.set_piece(Position::new_unchecked(2, 0), Piece::new(PieceType::King, Color::White));
+        game.current_player = Color::Black;
+        
+        assert_eq!(game.legal_move_count(), 0);
+        assert!(!game.board.is_king_in_check(Color::Black));
+        
+        game.refresh_status();
+        assert_eq!(game.status, GameStatus::Stalemate);
+    }
+    
+    #[test]
+    fn test_compact_roundtrip() {
+        let game = scripted_game(80);
+        assert_eq!(game.move_history.len(), 80);
+        
+        let compact = game.to_compact();
+        assert_eq!(compact.initial_fen, GameState::new().to_fen());
+        assert_eq!(compact.moves.len(), 80);
+        
+        // Saving writes the compact form rather than a snapshot per move
+        let json = game.to_json().unwrap();
+        assert_eq!(json, serde_json::to_string(&compact).unwrap());
+        
+        let restored = GameState::from_json(&json).unwrap();
+        assert_eq!(restored.board, game.board);
+        assert_eq!(restored.current_player, game.current_player);
+        assert_eq!(restored.status, game.status);
+        assert_eq!(restored.move_history, game.move_history);
+        assert_eq!(restored.captured_pieces, game.captured_pieces);
+        assert_eq!(restored.to_fen(), game.to_fen());
+        
+        // Castling rights, en passant and the clocks of a set-up position survive too
+        let mut game = GameState::from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w Kq - 7 12").unwrap();
+        play(&mut game, &["e2e4", "d7d5", "e4e5", "f7f5"]);
+        let compact = game.to_compact();
+        assert_eq!(compact.initial_fen, "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w Kq - 7 12");
+        
+        let restored = GameState::from_compact(&compact).unwrap();
+        assert_eq!(restored.to_fen(), game.to_fen());
+        assert!(restored.is_legal(Position::from_notation("e5").unwrap(), Position::from_notation("f6").unwrap()));
+    }
+    
+    #[test]
+    fn test_compact_fen_without_clocks() {
+        // A save whose FEN stops after en passant counts moves from 1
+        let old_save = CompactGame {
+            initial_fen: String::from("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -"),
+            moves: vec![Move {
+                from: Position::from_notation("g1").unwrap(),
+                to: Position::from_notation("f3").unwrap(),
+                promotion_piece: None,
+            }],
+        };
+        let game = GameState::from_compact(&old_save).unwrap();
+        assert_eq!(game.fullmove_number, 1);
+        assert_eq!(game.halfmove_clock, 1);
+        assert_eq!(game.to_compact().initial_fen, "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    }
+    
+    #[test]
+    fn test_move_serializes_squares_algebraically() {
+        let e2 = Position::from_notation("e2").unwrap();
+        let e4 = Position::from_notation("e4").unwrap();
+        let game_move = Move { from: e2, to: e4, promotion_piece: None };
+        
+        let json = serde_json::to_string(&game_move).unwrap();
+        assert_eq!(json, r#"{"from":"e2","to":"e4","promotion_piece":null}"#);
+        assert_eq!(serde_json::from_str::<Move>(&json).unwrap(), game_move);
+        assert!(serde_json::from_str::<Move>(r#"{"from":"e2","to":"i9","promotion_piece":null}"#).is_err());
+        
+        // A position on its own stays numeric
+        assert_eq!(serde_json::to_string(&e4).unwrap(), r#"{"file":4,"rank":3}"#);
+    }
+    
+    #[test]
+    fn test_load_recomputes_stale_status() {
+        let mut game = GameState::new();
+        for san in ["e4", "e5", "Bc4", "Nc6", "Qh5", "Nf6", "Qxf7#"] {
+            game.apply_san(san).unwrap();
+        }
+        
+        // A save written with a status that doesn't match the board
+        game.status = GameStatus::InProgress;
+        let json = game.to_json().unwrap();
+        
+        let loaded = GameState::from_json(&json).unwrap();
+        assert_eq!(loaded.status, GameStatus::Checkmate { winner: Color::White });
+        assert_eq!(loaded.board, game.board);
+    }
+    
+    #[test]
+    fn test_load_rebuilds_caches() {
+        let mut game = GameState::new();
+        for san in ["e4", "e5", "Ke2", "Nf6"] {
+            game.apply_san(san).unwrap();
+        }
+        game.evaluate(|_| 0.5);
+        
+        let json = game.to_json().unwrap();
+        for cache in ["\"hash\"", "\"kings\"", "\"evaluation_cache\"", "\"observer\"", "\"clock\""] {
+            assert!(!json.contains(cache), "{} was saved", cache);
+        }
+        
+        let mut loaded = GameState::from_json(&json).unwrap();
+        assert_eq!(loaded.board.piece_hash(), game.board.piece_hash());
+        assert_eq!(loaded.zobrist_hash(), game.zobrist_hash());
+        assert_eq!(loaded.board.find_king(Color::White), Position::from_notation("e2"));
+        assert_eq!(loaded.board.find_king(Color::Black), Position::from_notation("e8"));
+        assert_eq!(loaded.cached_evaluation(), None);
+        assert_eq!(loaded.evaluate(|_| 1.0), 1.0);
+        
+        // Undo restores saved boards, whose caches must be rebuilt too
+        loaded.undo();
+        loaded.undo();
+        assert_eq!(loaded.board.find_king(Color::White), Position::from_notation("e1"));
+        assert_eq!(loaded.board.piece_hash(), Board::from_fen(&loaded.board.to_fen()).unwrap().piece_hash());
+    }
+    
+    fn play(game: &mut GameState, moves: &[&str]) {
+        for pair in moves {
+            let (from, to) = pair.split_at(2);
+            let game_move = Move {
+                from: Position::from_notation(from).unwrap(),
+                to: Position::from_notation(to).unwrap(),
+                promotion_piece: None,
+            };
+            game.make_move(&game_move).unwrap_or_else(|e| panic!("{} failed: {:?}", pair, e));
+        }
+    }
+    
+    #[test]
+    fn test_repetition_counts_knight_shuffle() {
+        let mut game = GameState::new();
+        assert_eq!(game.repetition_count(), 1);
+        
+        play(&mut game, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+        assert_eq!(game.repetition_count(), 2);
+        assert!(!game.has_threefold_repetition());
+        
+        play(&mut game, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+        assert!(game.has_threefold_repetition());
+        
+        game.undo();
+        assert!(!game.has_threefold_repetition());
+    }
+    
+    #[test]
+    fn test_repetition_respects_castling_rights() {
+        let mut game = GameState::new();
+        play(&mut game, &["g1f3", "g8f6"]);
+        let with_rights = game.position_key();
+        
+        // The rooks step out and back: same placement, but kingside castling is gone
+        play(&mut game, &["h1g1", "h8g8", "g1h1", "g8h8"]);
+        assert_ne!(game.position_key(), with_rights);
+        assert_eq!(game.castling_rights().to_fen(), "Qq");
+        
+        // Third time this placement occurs, but only the second with these rights
+        play(&mut game, &["h1g1", "h8g8", "g1h1", "g8h8"]);
+        assert_eq!(game.repetition_count(), 2);
+        assert!(!game.has_threefold_repetition());
+        
+        play(&mut game, &["h1g1", "h8g8", "g1h1", "g8h8"]);
+        assert!(game.has_threefold_repetition());
+    }
+    
+    #[test]
+    fn test_position_key_en_passant_only_when_capturable() {
+        let mut game = GameState::new();
+        
+        // No black pawn can take on e3
+        play(&mut game, &["e2e4"]);
+        assert!(game.position_key().ends_with(" b KQkq -"));
+        
+        // The e5 pawn could capture on d6
+        play(&mut game, &["a7a6", "e4e5", "d7d5"]);
+        assert!(game.position_key().ends_with(" w KQkq d6"));
+    }
+
+    #[test]
+    fn test_en_passant_capture() {
+        let mut game = GameState::new();
+        play(&mut game, &["e2e4", "a7a6", "e4e5", "d7d5"]);
+
+        let exd6 = Move { from: Position::new_unchecked(4, 4), to: Position::new_unchecked(3, 5), promotion_piece: None };
+        assert_eq!(game.move_kind(&exd6), MoveKind::EnPassant);
+        assert_eq!(game.to_san(&exd6).unwrap(), "exd6");
+
+        game.make_move(&exd6).unwrap();
+        assert_eq!(game.board.get_piece(&Position::new_unchecked(3, 5)), Some(&Piece::new(PieceType::Pawn, Color::White)));
+        assert!(game.board.get_piece(&Position::new_unchecked(3, 4)).is_none());
+        assert_eq!(game.board.piece_count(), 31);
+        assert_eq!(game.hash(), game.zobrist_hash());
+    }
+
+    /// Play `game_move`, undo it and check the game is back exactly where it was
+    fn assert_undo_restores(game: &mut GameState, game_move: Move) {
+        let before = game.clone();
+        game.make_move(&game_move).unwrap();
+        assert_ne!(game.board, before.board);
+        
+        assert_eq!(game.undo(), Some(game_move));
+        assert_eq!(game.board, before.board);
+        assert_eq!(game.to_fen(), before.to_fen());
+        assert_eq!(game.castling_rights(), before.castling_rights());
+        assert_eq!(game.en_passant_target, before.en_passant_target);
+        assert_eq!(game.promoted_pawns, before.promoted_pawns);
+        assert_eq!(game.hash(), before.hash());
+    }
+    
+    #[test]
+    fn test_undo_castling() {
+        let mut game = GameState::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let kingside = Move { from: Position::new_unchecked(4, 0), to: Position::new_unchecked(6, 0), promotion_piece: None };
+        assert_undo_restores(&mut game, kingside);
+        assert_eq!(game.board.get_piece(&Position::new_unchecked(7, 0)), Some(&Piece::new(PieceType::Rook, Color::White)));
+        assert!(game.castling_rights().white_kingside);
+    }
+    
+    #[test]
+    fn test_undo_promotion() {
+        let mut game = promotion_game();
+        let push = Move {
+            from: Position::new_unchecked(0, 6),
+            to: Position::new_unchecked(0, 7),
+            promotion_piece: Some(PieceType::Knight),
+        };
+        assert_undo_restores(&mut game, push);
+        assert_eq!(game.board.get_piece(&Position::new_unchecked(0, 6)), Some(&Piece::new(PieceType::Pawn, Color::White)));
+        assert!(game.board.get_piece(&Position::new_unchecked(0, 7)).is_none());
+    }
+    
+    #[test]
+    fn test_undo_en_passant() {
+        let mut game = GameState::new();
+        play(&mut game, &["e2e4", "a7a6", "e4e5", "d7d5"]);
+        let exd6 = Move { from: Position::new_unchecked(4, 4), to: Position::new_unchecked(3, 5), promotion_piece: None };
+        assert_undo_restores(&mut game, exd6);
+        assert_eq!(game.board.get_piece(&Position::new_unchecked(3, 4)), Some(&Piece::new(PieceType::Pawn, Color::Black)));
+        assert_eq!(game.en_passant_square(), Some(Position::new_unchecked(3, 5)));
+    }
+    
+    #[test]
+    fn test_verify_consistency() {
+        let mut game = GameState::new();
+        assert_eq!(game.verify_consistency(), Ok(()));
+        // Captures and castling replay like any other move
+        for san in ["e4", "d5", "exd5", "Qxd5", "Nc3", "Qa5", "d4", "Nc6", "Nf3", "Bg4", "Be2", "O-O-O"] {
+            game.apply_san(san).unwrap();
+        }
+        assert_eq!(game.verify_consistency(), Ok(()));
+        
+        // An extra queen placed directly on the board isn't explained by any move
+        let h3 = Position::from_notation("h3").unwrap();
+        game.board.set_piece(h3, Piece::new(PieceType::Queen, Color::White));
+        assert_eq!(game.verify_consistency(), Err(ConsistencyError::BoardMismatch(vec![h3])));
+        
+        // Nor is one placed before any move was played, or before the first move
+        let mut game = GameState::new();
+        game.board.set_piece(h3, Piece::new(PieceType::Queen, Color::White));
+        assert_eq!(game.verify_consistency(), Err(ConsistencyError::BoardMismatch(vec![h3])));
+        game.apply_san("e4").unwrap();
+        assert_eq!(game.verify_consistency(), Err(ConsistencyError::BoardMismatch(vec![h3])));
+        
+        // A game set up from a FEN is checked against that position
+        let game = GameState::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert_eq!(game.verify_consistency(), Ok(()));
+    }
+    
+    #[test]
+    fn test_is_legal() {
+        let mut game = GameState::new();
+        let square = |name| Position::from_notation(name).unwrap();
+
+        // Turn-aware, and a pawn may not capture forwards
+        assert!(game.is_legal(square("e2"), square("e4")));
+        assert!(!game.is_legal(square("e7"), square("e5")));
+        assert!(!game.is_legal(square("e2"), square("e5")));
+
+        // En passant only right after the double step
+        play(&mut game, &["e2e4", "a7a6", "e4e5", "d7d5"]);
+        assert!(game.is_legal(square("e5"), square("d6")));
+        play(&mut game, &["b1c3", "a6a5"]);
+        assert!(!game.is_legal(square("e5"), square("d6")));
+
+        // Castling once the squares between king and rook are empty
+        play(&mut game, &["g1f3", "a5a4"]);
+        assert!(!game.is_legal(square("e1"), square("g1")));
+        play(&mut game, &["f1e2", "a4a3"]);
+        assert!(game.is_legal(square("e1"), square("g1")));
+        assert!(!game.is_legal(square("e1"), square("c1")));
+
+        // A knight pinned to its king can't move
+        let mut pinned = GameState::new();
+        pinned.board = Board::new();
+        pinned.board.set_piece(square("e1"), Piece::new(PieceType::King, Color::White));
+        pinned.board.set_piece(square("e3"), Piece::new(PieceType::Knight, Color::White));
+        pinned.board.set_piece(square("e8"), Piece::new(PieceType::Rook, Color::Black));
+        pinned.board.set_piece(square("a8"), Piece::new(PieceType::King, Color::Black));
+        assert!(!pinned.is_legal(square("e3"), square("c4")));
+        assert!(pinned.is_legal(square("e1"), square("d1")));
+    }
+
+    #[test]
+    fn test_move_times_follow_clock() {
+        let clock = Arc::new(MockClock::default());
+        let mut game = GameState::new();
+        
+        // Moves before a clock is attached aren't timed
+        play(&mut game, &["e2e4"]);
+        assert!(game.move_times().is_empty());
+        
+        game.attach_clock(clock.clone());
+        clock.advance(Duration::from_secs(3));
+        play(&mut game, &["e7e5"]);
+        clock.advance(Duration::from_millis(1500));
+        play(&mut game, &["g1f3"]);
+        clock.advance(Duration::from_secs(12));
+        play(&mut game, &["b8c6"]);
+        
+        assert_eq!(game.move_times(), &[
+            Duration::from_secs(3),
+            Duration::from_millis(1500),
+            Duration::from_secs(12),
+        ]);
+        
+        game.undo();
+        assert_eq!(game.move_times().len(), 2);
+        
+        // The undone move's thinking time counts towards the move played instead
+        clock.advance(Duration::from_secs(2));
+        play(&mut game, &["g8f6"]);
+        assert_eq!(game.move_times()[2], Duration::from_secs(14));
+    }
+    
+    #[test]
+    fn test_apply_san_scholars_mate() {
+        let mut game = GameState::new();
+        
+        for san in ["e4", "e5", "Bc4", "Nc6", "Qh5", "Nf6??"] {
+            game.apply_san(san).unwrap();
+        }
+        let mate = game.apply_san("Qxf7#").unwrap();
+        
+        assert_eq!(mate, Move {
+            from: Position::from_notation("h5").unwrap(),
+            to: Position::from_notation("f7").unwrap(),
+            promotion_piece: None,
+        });
+        assert_eq!(game.status, GameStatus::Checkmate { winner: Color::White });
+    }
+    
+    #[test]
+    fn test_to_san() {
+        let mut game = GameState::new();
+        let san = |game: &GameState, pair: &str| {
+            let (from, to) = pair.split_at(2);
+            game.to_san(&Move {
+                from: Position::from_notation(from).unwrap(),
+                to: Position::from_notation(to).unwrap(),
+                promotion_piece: None,
+            }).unwrap()
+        };
+        
+        assert_eq!(san(&game, "e2e4"), "e4");
+        assert_eq!(san(&game, "g1f3"), "Nf3");
+        
+        play(&mut game, &["d2d4", "a7a6", "c1f4", "b7b6", "g1f3", "c7c6"]);
+        assert_eq!(san(&game, "b1d2"), "Nbd2");
+        assert_eq!(san(&game, "f4b8"), "Bxb8");
+        
+        play(&mut game, &["e2e4", "d7d5"]);
+        assert_eq!(san(&game, "e4d5"), "exd5");
+        
+        let mut mate = GameState::new();
+        for step in ["e4", "e5", "Bc4", "Nc6", "Qh5", "Nf6"] {
+            mate.apply_san(step).unwrap();
+        }
+        assert_eq!(san(&mate, "h5f7"), "Qxf7#");
+        
+        let unmarked = SanStyle { check_markers: false, ..SanStyle::default() };
+        let qxf7 = Move {
+            from: Position::from_notation("h5").unwrap(),
+            to: Position::from_notation("f7").unwrap(),
+            promotion_piece: None,
+        };
+        assert_eq!(mate.to_san_with_style(&qxf7, unmarked).unwrap(), "Qxf7");
+        
+        assert_eq!(san(&promotion_game(), "a7a8"), "a8=Q+");
+    }
+    
+    #[test]
+    fn test_to_pgn() {
+        let mut game = GameState::new();
+        assert_eq!(game.to_pgn(), "*");
+        
+        for step in ["e4", "e5", "Bc4", "Nc6", "Qh5"] {
+            game.apply_san(step).unwrap();
+        }
+        assert_eq!(game.to_pgn(), "1. e4 e5 2. Bc4 Nc6 3. Qh5 *");
+        
+        game.apply_san("Nf6").unwrap();
+        game.apply_san("Qxf7").unwrap();
+        assert_eq!(game.to_pgn(), "1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# 1-0");
+        
+        // Numbering continues from a loaded position, and resignations and draws are scored
+        let mut loaded = GameState::from_fen("4k3/8/8/8/8/8/8/4K2R b K - 3 40").unwrap();
+        assert_eq!(loaded.to_pgn(), "*");
+        loaded.apply_san("Kd7").unwrap();
+        loaded.apply_san("O-O").unwrap();
+        assert_eq!(loaded.to_pgn(), "40... Kd7 41. O-O *");
+        let zeros = SanStyle { zero_castling: true, ..SanStyle::default() };
+        assert_eq!(loaded.to_pgn_with_style(zeros), "40... Kd7 41. 0-0 *");
+        
+        let mut drawn = loaded.clone();
+        loaded.resign(Color::White);
+        assert!(loaded.to_pgn().ends_with(" 0-1"));
+        drawn.agree_draw();
+        assert!(drawn.to_pgn().ends_with(" 1/2-1/2"));
+    }
+    
+    #[test]
+    fn test_parse_san_errors() {
+        let mut game = GameState::new();
+        
+        assert_eq!(game.parse_san("e5"), Err(SanError::NoMatchingMove));
+        assert_eq!(game.parse_san("Ke2"), Err(SanError::NoMatchingMove));
+        assert_eq!(game.parse_san("z9"), Err(SanError::InvalidSyntax));
+        assert_eq!(game.parse_san(""), Err(SanError::InvalidSyntax));
+        
+        // Both knights can reach d2 once the pawn and bishop are out of the way
+        play(&mut game, &["d2d4", "a7a6", "c1f4", "b7b6", "g1f3", "c7c6"]);
+        let square = |name| Position::from_notation(name).unwrap();
+        let knight_to_d2 = |from| Move { from: square(from), to: square("d2"), promotion_piece: None };
+        assert_eq!(game.parse_san("Nd2"), Err(SanError::Ambiguous(vec![knight_to_d2("b1"), knight_to_d2("f3")])));
+        assert_eq!(game.parse_san("Nbd2").unwrap().from, Position::from_notation("b1").unwrap());
+        assert_eq!(game.parse_san("Nfd2").unwrap().from, Position::from_notation("f3").unwrap());
+    }
+    
+    #[test]
+    fn test_threefold_is_claimable_not_automatic() {
+        let mut game = GameState::new();
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        
+        play(&mut game, &shuffle);
+        play(&mut game, &shuffle);
+        assert_eq!(game.repetition_count(), 3);
+        
+        assert_eq!(game.get_game_result(), None);
+        assert_eq!(game.status, GameStatus::InProgress);
+        assert_eq!(game.claimable_draws(), vec![DrawReason::ThreefoldRepetition]);
+    }
+    
+    #[test]
+    fn test_no_moves_in_dead_position() {
+        let mut game = GameState::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(game.status, GameStatus::Draw(DrawReason::DeadPosition));
+        
+        let king_step = Move {
+            from: Position::from_notation("e1").unwrap(),
+            to: Position::from_notation("d1").unwrap(),
+            promotion_piece: None,
+        };
+        assert_eq!(game.make_move(&king_step), Err(MoveError::GameOver));
+        assert_eq!(game.status, GameStatus::Draw(DrawReason::DeadPosition));
+    }
+    
+    #[test]
+    fn test_no_moves_after_resigning() {
+        let mut game = GameState::new();
+        game.resign(Color::White);
+        
+        let e4 = Move {
+            from: Position::from_notation("e2").unwrap(),
+            to: Position::from_notation("e4").unwrap(),
+            promotion_piece: None,
+        };
+        assert_eq!(game.make_move(&e4), Err(MoveError::GameOver));
+        assert_eq!(game.status, GameStatus::Resigned { winner: Color::Black });
+        assert!(game.move_history.is_empty());
+    }
+    
+    #[test]
+    fn test_claim_threefold_repetition() {
+        let mut game = GameState::new();
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        
+        play(&mut game, &shuffle);
+        assert_eq!(game.claim_draw(), None);
+        assert!(!game.is_game_over());
+        
+        play(&mut game, &shuffle);
+        assert_eq!(game.claim_draw(), Some(DrawReason::ThreefoldRepetition));
+        assert_eq!(game.status, GameStatus::Draw(DrawReason::ThreefoldRepetition));
+        assert_eq!(game.get_game_result(), Some(GameResult::Draw(DrawReason::ThreefoldRepetition)));
+        assert!(game.claimable_draws().is_empty());
+        
+        // The claim ends the game, so no more moves can be made
+        assert_eq!(game.make_move(&Move {
+            from: Position::from_notation("e2").unwrap(),
+            to: Position::from_notation("e4").unwrap(),
+            promotion_piece: None,
+        }), Err(MoveError::GameOver));
+        assert_eq!(game.status, GameStatus::Draw(DrawReason::ThreefoldRepetition));
+        
+        // Taking back the repeating move takes back the claim
+        game.undo();
+        assert!(!game.is_game_over());
+    }
+    
+    #[test]
+    fn test_undo_past_move_rule_draws() {
+        let rook_lift = Move {
+            from: Position::from_notation("a2").unwrap(),
+            to: Position::from_notation("a3").unwrap(),
+            promotion_piece: None,
+        };
+        
+        // The move reaching seventy-five moves ends the game, and undoing it resumes play
+        let mut game = GameState::from_fen("4k3/8/8/8/8/8/R7/4K3 w - - 149 100").unwrap();
+        game.make_move(&rook_lift).unwrap();
+        assert_eq!(game.get_game_result(), Some(GameResult::Draw(DrawReason::SeventyFiveMoveRule)));
+        game.undo();
+        assert_eq!(game.halfmove_clock, 149);
+        assert_eq!(game.status, GameStatus::InProgress);
+        assert!(!game.is_game_over());
+        assert!(game.validate_move(&rook_lift).is_ok());
+        
+        // Likewise for a claimed fifty-move draw
+        let mut game = GameState::from_fen("4k3/8/8/8/8/8/R7/4K3 w - - 99 60").unwrap();
+        game.make_move(&rook_lift).unwrap();
+        assert_eq!(game.claim_draw(), Some(DrawReason::FiftyMoveRule));
+        game.undo();
+        assert!(!game.is_game_over());
+        assert!(game.claimable_draws().is_empty());
+    }
+    
+    #[test]
+    fn test_capture_resets_halfmove_clock() {
+        let mut game = GameState::from_fen("4k3/8/8/8/8/8/p7/R3K3 w - - 97 70").unwrap();
+        play(&mut game, &["e1d1", "e8d8"]);
+        assert_eq!(game.halfmove_clock, 99);
+        
+        play(&mut game, &["a1a2"]);
+        assert_eq!(game.halfmove_clock, 0);
+        assert!(game.claimable_draws().is_empty());
+        
+        game.undo();
+        assert_eq!(game.halfmove_clock, 99);
+        play(&mut game, &["d1e1"]);
+        assert_eq!(game.claimable_draws(), vec![DrawReason::FiftyMoveRule]);
+    }
+    
+    #[test]
+    fn test_fifty_move_draw_after_quiet_moves() {
+        // Rooks, knights and kings shuffle to a new position every move without giving
+        // check, so no position repeats
+        let mut game = GameState::from_fen("rn2k3/8/8/8/8/8/8/RN2K3 w - - 0 1").unwrap();
+        let mut seen = HashSet::from([game.position_key()]);
+        for ply in 0..100 {
+            assert!(!game.is_fifty_move_draw(), "draw after {} plies", ply);
+            let quiet = game.generate_legal_moves().into_iter().find(|game_move| {
+                let mut after = game.clone();
+                game.board.get_piece(&game_move.to).is_none()
+                    && after.make_move(game_move).is_ok()
+                    && !after.is_in_check()
+                    && !seen.contains(&after.position_key())
+            }).unwrap_or_else(|| panic!("no quiet move to a new position after {} plies in {}", ply, game.to_fen()));
+            game.make_move(&quiet).unwrap();
+            seen.insert(game.position_key());
+        }
+        
+        // The draw may be claimed, but the game goes on until it is
+        assert!(game.is_fifty_move_draw());
+        assert_eq!(game.claimable_draws(), vec![DrawReason::FiftyMoveRule]);
+        assert!(!game.is_game_over());
+    }
+    
+    #[test]
+    fn test_undo_third_repetition() {
+        let mut game = GameState::new();
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        play(&mut game, &shuffle);
+        play(&mut game, &shuffle);
+        assert!(game.has_threefold_repetition());
+        
+        game.undo();
+        assert_eq!(game.repetition_count(), 2);
+        assert!(!game.has_threefold_repetition());
+        assert!(game.claimable_draws().is_empty());
+        
+        // Replaying the move repeats the position for the third time again
+        play(&mut game, &["f6g8"]);
+        assert_eq!(game.repetition_count(), 3);
+        assert_eq!(game.claimable_draws(), vec![DrawReason::ThreefoldRepetition]);
+    }
+    
+    #[test]
+    fn test_king_cache_matches_scan_through_castling() {
+        let assert_cache = |game: &GameState| {
+            for color in [Color::White, Color::Black] {
+                assert_eq!(game.board.find_king(color), game.board.king_position(color), "{:?} king in {}", color, game.to_fen());
+            }
+        };
+        
+        // Castling on both sides, then each king stepping on
+        let mut game = GameState::new();
+        let moves = ["e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "d7d6", "e1g1", "c8e6", "d2d3", "d8d7", "f1e1", "e8c8", "g1h1", "c8b8"];
+        for coordinate in moves {
+            play(&mut game, &[coordinate]);
+            assert_cache(&game);
+        }
+        assert_eq!(game.board.find_king(Color::White), Position::from_notation("h1"));
+        assert_eq!(game.board.find_king(Color::Black), Position::from_notation("b8"));
+        
+        while game.undo().is_some() {
+            assert_cache(&game);
+        }
+        assert_eq!(game.board.find_king(Color::White), Position::from_notation("e1"));
+    }
+    
+    #[test]
+    fn test_claim_draw_with_move_completing_threefold() {
+        let mut game = GameState::new();
+        play(&mut game, &["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1"]);
+        
+        // The start position has occurred twice; Black's knight returning makes it three
+        let back = Move {
+            from: Position::from_notation("f6").unwrap(),
+            to: Position::from_notation("g8").unwrap(),
+            promotion_piece: None,
+        };
+        assert!(!game.can_claim_draw());
+        assert!(game.can_claim_draw_with(&back));
+        
+        let elsewhere = Move { to: Position::from_notation("h5").unwrap(), ..back };
+        assert!(!game.can_claim_draw_with(&elsewhere));
+        
+        game.make_move(&back).unwrap();
+        assert!(game.can_claim_draw());
+    }
+    
+    #[test]
+    fn test_fivefold_ends_game() {
+        let mut game = GameState::new();
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        
+        for _ in 0..4 {
+            play(&mut game, &shuffle);
+        }
+        assert_eq!(game.repetition_count(), 5);
+        
+        assert_eq!(game.get_game_result(), Some(GameResult::Draw(DrawReason::FivefoldRepetition)));
+        assert_eq!(game.status, GameStatus::Draw(DrawReason::FivefoldRepetition));
+        assert!(game.claimable_draws().is_empty());
+    }
+
+    #[test]
+    fn test_fen_move_counters() {
+        let mut game = GameState::new();
+        assert_eq!(game.to_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        play(&mut game, &["g1f3", "g8f6", "b1c3"]);
+        assert_eq!(game.to_fen(), "rnbqkb1r/pppppppp/5n2/8/8/2N2N2/PPPPPPPP/R1BQKB1R b KQkq - 3 2");
+
+        let fen = "4k3/8/8/8/8/8/8/R3K3 w Q - 99 73";
+        let mut game = GameState::from_fen(fen).unwrap();
+        assert_eq!(game.to_fen(), fen);
+        assert!(!game.can_claim_draw());
+
+        // A quiet rook move completes fifty moves by each side
+        play(&mut game, &["a1a2"]);
+        assert_eq!(game.claimable_draws(), vec![DrawReason::FiftyMoveRule]);
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/R7/4K3 b - - 100 73");
+
+        assert!(GameState::from_fen("4k3/8/8/8/8/8/8/R3K3 w Q - x 1").is_none());
+    }
+
+    #[test]
+    fn test_perft_start_position() {
+        let mut game = GameState::new();
+        
+        assert_eq!(game.generate_legal_moves().len(), 20);
+        assert_eq!(game.perft(1), 20);
+        assert_eq!(game.perft(2), 400);
+        assert_eq!(game.perft(3), 8902);
+    }
+
+    #[test]
+    fn test_legal_moves_of_type() {
+        let game = GameState::new();
+        
+        let knight_moves = game.legal_moves_of_type(Color::White, PieceType::Knight);
+        assert_eq!(knight_moves.len(), 4);
+        assert!(knight_moves.iter().all(|m| game.board.get_piece(&m.from).unwrap().piece_type == PieceType::Knight));
+        
+        assert_eq!(game.legal_moves_of_type(Color::White, PieceType::Pawn).len(), 16);
+        assert!(game.legal_moves_of_type(Color::Black, PieceType::Queen).is_empty());
+        
+        // Castling and en passant are included for the side to move
+        let game = GameState::from_fen("4k3/8/8/3pP3/8/8/8/4K2R w K d6 0 1").unwrap();
+        assert!(game.legal_moves_of_type(Color::White, PieceType::King).contains(&Move {
+            from: Position::from_notation("e1").unwrap(),
+            to: Position::from_notation("g1").unwrap(),
+            promotion_piece: None,
+        }));
+        assert!(game.legal_moves_of_type(Color::White, PieceType::Pawn).contains(&Move {
+            from: Position::from_notation("e5").unwrap(),
+            to: Position::from_notation("d6").unwrap(),
+            promotion_piece: None,
+        }));
+    }
+    
+    #[test]
+    fn test_castling_moves_generated() {
+        let mut game = GameState::new();
+        for file in [1, 2, 3, 5, 6] {
+            game.board.remove_piece(&Position::new_unchecked(file, 0));
+        }
+
+        let king = Position::new_unchecked(4, 0);
+        let kingside = Move { from: king, to: Position::new_unchecked(6, 0), promotion_piece: None };
+        let queenside = Move { from: king, to: Position::new_unchecked(2, 0), promotion_piece: None };
+        let moves = game.generate_legal_moves();
+        assert!(moves.contains(&kingside));
+        assert!(moves.contains(&queenside));
+        assert_eq!(game.move_kind(&kingside), MoveKind::Castle);
+        assert_eq!(game.to_san(&queenside).unwrap(), "O-O-O");
+        
+        let zeros = SanStyle { zero_castling: true, ..SanStyle::default() };
+        assert_eq!(game.to_san_with_style(&kingside, zeros).unwrap(), "0-0");
+        assert_eq!(game.to_san_with_style(&queenside, zeros).unwrap(), "0-0-0");
+
+        game.make_move(&kingside).unwrap();
+        assert_eq!(game.board.get_piece(&Position::new_unchecked(6, 0)), Some(&Piece::new(PieceType::King, Color::White)));
+        assert_eq!(game.board.get_piece(&Position::new_unchecked(5, 0)), Some(&Piece::new(PieceType::Rook, Color::White)));
+        assert!(game.board.get_piece(&Position::new_unchecked(7, 0)).is_none());
+        assert!(!game.castling_rights().white_queenside);
+        assert_eq!(game.hash(), game.zobrist_hash());
+    }
+
+    #[test]
+    fn test_no_castling_through_attacked_square() {
+        let mut game = GameState::new();
+        for file in [5, 6] {
+            game.board.remove_piece(&Position::new_unchecked(file, 0));
+        }
+        // Open the f-file and put a black rook on it
+        game.board.remove_piece(&Position::new_unchecked(5, 1));
+        game.board.remove_piece(&Position::new_unchecked(5, 6));
+        game.board.remove_piece(&Position::new_unchecked(7, 7));
+        game.board.set_piece(Position::new_unchecked(5, 6), Piece::new(PieceType::Rook, Color::Black));
+
+        let kingside = Move { from: Position::new_unchecked(4, 0), to: Position::new_unchecked(6, 0), promotion_piece: None };
+        assert!(!game.generate_legal_moves().contains(&kingside));
+        assert_eq!(game.make_move(&kingside), Err(MoveError::IllegalMove));
+    }
+
+    #[test]
+    fn test_observer_sees_mate_in_order() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut game = GameState::new();
+        for san in ["e4", "e5", "Bc4", "Nc6", "Qh5", "Nf6"] {
+            game.apply_san(san).unwrap();
+        }
+        
+        let recorded = events.clone();
+        game.set_observer(move |event| recorded.lock().unwrap().push(*event));
+        let mate = game.apply_san("Qxf7#").unwrap();
+        assert_eq!(*events.lock().unwrap(), vec![
+            GameEvent::MoveMade(mate),
+            GameEvent::Checkmate(Color::White),
+        ]);
+        
+        // Previews don't notify
+        events.lock().unwrap().clear();
+        game.undo();
+        game.preview_move(&mate).unwrap();
+        game.reset();
+        assert_eq!(*events.lock().unwrap(), vec![GameEvent::MoveUndone(mate), GameEvent::GameReset]);
+        
+        // Resigning and agreeing a draw notify too, but only while the game is still going
+        events.lock().unwrap().clear();
+        game.resign(Color::Black);
+        game.agree_draw();
+        assert_eq!(game.status, GameStatus::Resigned { winner: Color::White });
+        game.reset();
+        game.agree_draw();
+        game.resign(Color::White);
+        assert_eq!(game.status, GameStatus::Draw(DrawReason::Agreement));
+        assert_eq!(*events.lock().unwrap(), vec![
+            GameEvent::Resigned(Color::White),
+            GameEvent::GameReset,
+            GameEvent::Draw(DrawReason::Agreement),
+        ]);
+    }
+
+    #[test]
+    fn test_reset_keeps_configuration() {
+        let clock = Arc::new(MockClock::default());
+        let mut game = GameState::new();
+        game.default_promotion = PieceType::Knight;
+        game.attach_clock(clock.clone());
+        play(&mut game, &["e2e4", "e7e5", "g1f3", "b8c6"]);
+
+        game.reset();
+        assert_eq!(game.board, Board::new_game());
+        assert_eq!(game.current_player, Color::White);
+        assert_eq!(game.status, GameStatus::InProgress);
+        assert!(game.get_last_move().is_none());
+        assert_eq!(game.castling_rights(), CastlingRights::all());
+        assert_eq!(game.default_promotion, PieceType::Knight);
+
+        // The clock stays attached and times the new game's moves
+        assert!(game.move_times().is_empty());
+        clock.advance(Duration::from_secs(3));
+        play(&mut game, &["d2d4"]);
+        assert_eq!(game.move_times(), &[Duration::from_secs(3)]);
+    }
+
+    #[test]
+    fn test_incremental_hash_matches_fresh() {
+        let mut game = GameState::new();
+        assert_eq!(game.hash(), game.zobrist_hash());
+        
+        // Quiet moves, a double step allowing en passant, captures, rook and king moves
+        // losing castling rights, and undos in between
+        let moves = ["e2e4", "d7d5", "e4d5", "d8d5", "b1c3", "d5a5", "a1b1", "e8d8",
+                     "e1e2", "c7c5", "e2e1", "c5c4", "d2d4", "a5a4"];
+        for (i, pair) in moves.iter().enumerate() {
+            play(&mut game, &[pair]);
+            assert_eq!(game.hash(), game.zobrist_hash(), "after {}", pair);
+            
+            if i % 3 == 2 {
+                game.undo();
+                assert_eq!(game.hash(), game.zobrist_hash(), "after undoing {}", pair);
+                play(&mut game, &[pair]);
+            }
+        }
+        
+        // Side to move is part of the hash
+        let hash = game.hash();
+        game.switch_turn();
+        assert_ne!(game.hash(), hash);
+        
+        while game.undo().is_some() {
+            assert_eq!(game.hash(), game.zobrist_hash());
+        }
+        assert_eq!(game.hash(), GameState::new().hash());
+    }
+    
+    #[test]
+    fn test_hash_transpositions() {
+        let mut kingside_first = GameState::new();
+        play(&mut kingside_first, &["g1f3", "g8f6", "b1c3", "b8c6"]);
+        let mut queenside_first = GameState::new();
+        play(&mut queenside_first, &["b1c3", "b8c6", "g1f3", "g8f6"]);
+        assert_eq!(kingside_first.hash(), queenside_first.hash());
+        assert_eq!(kingside_first.zobrist_hash(), queenside_first.zobrist_hash());
+        
+        // Moving the knights out and back restores the hash, but the king walking out and
+        // back costs the castling rights, so it doesn't
+        let mut knights = GameState::new();
+        play(&mut knights, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+        assert_eq!(knights.hash(), GameState::new().hash());
+        
+        let mut kings = GameState::new();
+        play(&mut kings, &["e2e4", "e7e5", "e1e2", "e8e7", "e2e1", "e7e8"]);
+        let mut pawns_only = GameState::new();
+        play(&mut pawns_only, &["e2e4", "e7e5"]);
+        assert_eq!(kings.board.piece_hash(), pawns_only.board.piece_hash());
+        assert_ne!(kings.hash(), pawns_only.hash());
+    }
+    
+    #[test]
+    fn test_dead_position_is_drawn() {
+        let mut game = GameState::new();
+        game.board = Board::new();
+        game.board.set_piece(Position::new_unchecked(4, 0), Piece::new(PieceType::King, Color::White));
+        game.board.set_piece(Position::new_unchecked(2, 3), Piece::new(PieceType::Knight, Color::White));
+        game.board.set_piece(Position::new_unchecked(4, 7), Piece::new(PieceType::King, Color::Black));
+        game.board.set_piece(Position::new_unchecked(3, 5), Piece::new(PieceType::Pawn, Color::Black));
+        
+        // Knight takes the last pawn, leaving king and knight against king
+        let nxd6 = Move { from: Position::new_unchecked(2, 3), to: Position::new_unchecked(3, 5), promotion_piece: None };
+        assert_eq!(game.preview_move(&nxd6).unwrap().status, GameStatus::Draw(DrawReason::DeadPosition));
+    }
+    
+    #[test]
+    fn test_pinned_pieces_for_side_to_move() {
+        // With the d-pawn gone from d2, the bishop on b4 pins the knight on c3 to the king
+        let mut game = GameState::new();
+        for san in ["e4", "e5", "Nc3", "Bb4", "d3", "Nf6"] {
+            game.apply_san(san).unwrap();
+        }
+        
+        let c3 = Position::from_notation("c3").unwrap();
+        let b4 = Position::from_notation("b4").unwrap();
+        assert_eq!(game.pinned_pieces(), vec![(c3, b4)]);
+        
+        // Only pins against the side to move are reported
+        game.apply_san("a3").unwrap();
+        assert!(game.pinned_pieces().is_empty());
+    }
+    
+    #[test]
+    fn test_is_in_check_after_checking_move() {
+        let mut game = GameState::new();
+        play(&mut game, &["e2e4", "d7d6"]);
+        assert!(!game.is_in_check());
+        
+        game.apply_san("Bb5+").unwrap();
+        assert_eq!(game.current_player, Color::Black);
+        assert!(game.is_in_check());
+        assert!(!game.is_opponent_in_check());
+    }
+    
+    #[test]
+    fn test_status_descriptions() {
+        let cases = [
+            (GameStatus::InProgress, "Game in progress"),
+            (GameStatus::Check { player: Color::Black }, "Black is in check"),
+            (GameStatus::Checkmate { winner: Color::White }, "White wins by checkmate"),
+            (GameStatus::Stalemate, "Draw by stalemate"),
+            (GameStatus::Draw(DrawReason::DeadPosition), "Draw by dead position"),
+            (GameStatus::Draw(DrawReason::ThreefoldRepetition), "Draw by threefold repetition"),
+            (GameStatus::Draw(DrawReason::FivefoldRepetition), "Draw by fivefold repetition"),
+            (GameStatus::Draw(DrawReason::FiftyMoveRule), "Draw by the fifty-move rule"),
+            (GameStatus::Draw(DrawReason::SeventyFiveMoveRule), "Draw by the seventy-five-move rule"),
+            (GameStatus::Draw(DrawReason::Agreement), "Draw by agreement"),
+            (GameStatus::Resigned { winner: Color::Black }, "Black wins by resignation"),
+        ];
+        
+        for (status, sentence) in cases {
+            assert_eq!(status.describe(), sentence);
         }
     }
 }
@@ -21,6 +21,25 @@ impl Color {
             Color::Black => Color::White,
         }
     }
+
+    /// Returns the index of this color for per-color arrays (White = 0, Black = 1).
+    pub fn index(&self) -> usize {
+        match self {
+            Color::White => 0,
+            Color::Black => 1,
+        }
+    }
+
+    /// Returns the color for a per-color array index.
+    ///
+    /// Returns `None` for any index other than 0 or 1.
+    pub fn from_index(index: usize) -> Option<Self> {
+        match index {
+            0 => Some(Color::White),
+            1 => Some(Color::Black),
+            _ => None,
+        }
+    }
 }
 
 /// Represents the type of a chess piece.
@@ -34,6 +53,45 @@ pub enum PieceType {
     Pawn,
 }
 
+impl PieceType {
+    /// Returns the canonical rank of this piece type by value, from the pawn (0) up to the king (5).
+    ///
+    /// Use this rather than the declaration order of the variants when sorting pieces for display.
+    pub fn value_rank(&self) -> u8 {
+        match self {
+            PieceType::Pawn => 0,
+            PieceType::Knight => 1,
+            PieceType::Bishop => 2,
+            PieceType::Rook => 3,
+            PieceType::Queen => 4,
+            PieceType::King => 5,
+        }
+    }
+}
+
+/// Orders piece types by value (see `PieceType::value_rank`), for sorting captured pieces.
+///
+/// ```
+/// # use chess_app::types::{ByValue, PieceType};
+/// let mut captured = vec![ByValue(PieceType::Queen), ByValue(PieceType::Pawn)];
+/// captured.sort();
+/// assert_eq!(captured[0].0, PieceType::Pawn);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByValue(pub PieceType);
+
+impl Ord for ByValue {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.value_rank().cmp(&other.0.value_rank())
+    }
+}
+
+impl PartialOrd for ByValue {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 /// Represents a chess piece with its type and color.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Piece {
@@ -46,6 +104,59 @@ impl Piece {
     pub fn new(piece_type: PieceType, color: Color) -> Self {
         Piece { piece_type, color }
     }
+
+    /// Creates a piece from its FEN letter (uppercase for White, lowercase for Black).
+    ///
+    /// Returns `None` if the character isn't one of `pnbrqkPNBRQK`.
+    pub fn from_fen_char(c: char) -> Option<Self> {
+        let piece_type = match c.to_ascii_lowercase() {
+            'p' => PieceType::Pawn,
+            'n' => PieceType::Knight,
+            'b' => PieceType::Bishop,
+            'r' => PieceType::Rook,
+            'q' => PieceType::Queen,
+            'k' => PieceType::King,
+            _ => return None,
+        };
+        let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+
+        Some(Piece { piece_type, color })
+    }
+
+    /// Returns the FEN letter for this piece (uppercase for White, lowercase for Black).
+    pub fn to_fen_char(&self) -> char {
+        let c = match self.piece_type {
+            PieceType::Pawn => 'p',
+            PieceType::Knight => 'n',
+            PieceType::Bishop => 'b',
+            PieceType::Rook => 'r',
+            PieceType::Queen => 'q',
+            PieceType::King => 'k',
+        };
+
+        match self.color {
+            Color::White => c.to_ascii_uppercase(),
+            Color::Black => c,
+        }
+    }
+
+    /// Returns the Unicode chess glyph for this piece, e.g. '♔' for the white king.
+    pub fn unicode_symbol(self) -> &'static str {
+        match (self.piece_type, self.color) {
+            (PieceType::King, Color::White) => "♔",
+            (PieceType::Queen, Color::White) => "♕",
+            (PieceType::Rook, Color::White) => "♖",
+            (PieceType::Bishop, Color::White) => "♗",
+            (PieceType::Knight, Color::White) => "♘",
+            (PieceType::Pawn, Color::White) => "♙",
+            (PieceType::King, Color::Black) => "♚",
+            (PieceType::Queen, Color::Black) => "♛",
+            (PieceType::Rook, Color::Black) => "♜",
+            (PieceType::Bishop, Color::Black) => "♝",
+            (PieceType::Knight, Color::Black) => "♞",
+            (PieceType::Pawn, Color::Black) => "♟",
+        }
+    }
 }
 
 /// Represents a position on the chess board.
@@ -63,10 +174,68 @@ impl Position {
     /// Creates a new position if the coordinates are valid.
     ///
     /// Returns `None` if either coordinate is outside the 0-7 range.
-    pub fn new(file: u8, rank: u8) -> Self {
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use chess_app::types::Position;
+    /// assert_eq!(Position::new(4, 3), Position::from_notation("e4"));
+    /// assert!(Position::new(8, 0).is_none());
+    /// ```
+    pub fn new(file: u8, rank: u8) -> Option<Self> {
+        let pos = Position { file, rank };
+        pos.is_valid().then_some(pos)
+    }
+
+    /// Creates a new position without checking the coordinates.
+    ///
+    /// Only for coordinates already known to be in the 0-7 range, such as loop
+    /// indices over the board.
+    pub fn new_unchecked(file: u8, rank: u8) -> Self {
+        debug_assert!(file < 8 && rank < 8, "position out of range: file={}, rank={}", file, rank);
         Position { file, rank }
     }
 
+    /// Creates a new position from signed coordinates, such as the result of stepping away
+    /// from another square.
+    ///
+    /// Returns `None` if either coordinate is outside the 0-7 range.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use chess_app::types::Position;
+    /// assert_eq!(Position::try_new(4, 3), Position::from_notation("e4"));
+    /// assert!(Position::try_new(-1, 0).is_none());
+    /// ```
+    pub fn try_new(file: i8, rank: i8) -> Option<Self> {
+        ((0..8).contains(&file) && (0..8).contains(&rank))
+            .then(|| Position::new_unchecked(file as u8, rank as u8))
+    }
+
+    /// Returns the square `file_delta` files and `rank_delta` ranks away, if it's on the board.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use chess_app::types::Position;
+    /// let e4 = Position::from_notation("e4").unwrap();
+    /// assert_eq!(e4.offset(1, 2), Position::from_notation("f6"));
+    ///
+    /// // Stepping off an edge doesn't wrap around to the other side
+    /// let a1 = Position::from_notation("a1").unwrap();
+    /// assert!(a1.offset(-1, 0).is_none());
+    /// ```
+    pub fn offset(&self, file_delta: i8, rank_delta: i8) -> Option<Position> {
+        if !self.is_valid() {
+            return None;
+        }
+        Position::try_new(
+            (self.file as i8).checked_add(file_delta)?,
+            (self.rank as i8).checked_add(rank_delta)?,
+        )
+    }
+
     /// Creates a new position from standard chess notation.
     /// 
     /// Chess notation consists of a file letter (a-h) followed by a rank number (1-8).
@@ -84,14 +253,15 @@ impl Position {
     /// assert!(Position::from_notation("i9").is_none());
     /// ```
     pub fn from_notation(notation: &str) -> Option<Self> {
-        // Validate the notation format
-        if notation.len() != 2 {
+        // Validate the notation format, checking for ASCII first since `len` counts bytes
+        // and a single multibyte character like 'é' is two of them
+        if !notation.is_ascii() || notation.len() != 2 {
             return None;
         }
         
-        let chars: Vec<char> = notation.chars().collect();
-        let file_char = chars[0];
-        let rank_char = chars[1];
+        let bytes = notation.as_bytes();
+        let file_char = bytes[0] as char;
+        let rank_char = bytes[1] as char;
         
         // Convert file (a-h) to coordinate (0-7)
         let file = match file_char {
@@ -151,12 +321,147 @@ impl Position {
     pub fn is_valid(&self) -> bool {
         self.file < 8 && self.rank < 8
     }
+
+    /// Returns the color of the square itself: `White` for light squares and `Black` for
+    /// dark ones, so a1 is dark and h1 is light.
+    pub fn square_color(&self) -> Color {
+        if (self.file + self.rank).is_multiple_of(2) {
+            Color::Black
+        } else {
+            Color::White
+        }
+    }
+
+    /// Returns the squares next to this one, orthogonally or diagonally, that are on the board.
+    pub fn king_neighbors(&self) -> impl Iterator<Item = Position> {
+        self.offset_squares(&KING_OFFSETS)
+    }
+
+    /// Returns the squares a knight on this square jumps to that are on the board.
+    pub fn knight_neighbors(&self) -> impl Iterator<Item = Position> {
+        self.offset_squares(&KNIGHT_OFFSETS)
+    }
+
+    /// Returns the squares from this one outwards in steps of `file_step` files and
+    /// `rank_step` ranks, up to the edge of the board. The square itself isn't included,
+    /// and a step of (0, 0) gives no squares.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use chess_app::types::Position;
+    /// let c1 = Position::from_notation("c1").unwrap();
+    /// let diagonal: Vec<String> = c1.ray(1, 1).map(|pos| pos.to_notation()).collect();
+    /// assert_eq!(diagonal, ["d2", "e3", "f4", "g5", "h6"]);
+    /// ```
+    pub fn ray(&self, file_step: i8, rank_step: i8) -> impl Iterator<Item = Position> {
+        let first = self.offset(file_step, rank_step).filter(|_| (file_step, rank_step) != (0, 0));
+        std::iter::successors(first, move |pos| pos.offset(file_step, rank_step))
+    }
+
+    fn offset_squares(&self, offsets: &'static [(i8, i8); 8]) -> impl Iterator<Item = Position> {
+        let origin = *self;
+        offsets.iter().filter_map(move |&(file_step, rank_step)| origin.offset(file_step, rank_step))
+    }
+}
+
+/// (file, rank) steps to the squares around a king
+const KING_OFFSETS: [(i8, i8); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+
+/// (file, rank) steps of a knight's jumps
+const KNIGHT_OFFSETS: [(i8, i8); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+
+/// Serde helpers for maps keyed by `Position`.
+///
+/// JSON only allows string object keys, so these maps are written as a list of
+/// `(position, value)` pairs instead.
+pub(crate) mod position_map {
+    use std::collections::HashMap;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use super::Position;
+
+    pub fn serialize<V, S>(map: &HashMap<Position, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        V: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(map.iter())
+    }
+
+    pub fn deserialize<'de, V, D>(deserializer: D) -> Result<HashMap<Position, V>, D::Error>
+    where
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let pairs = Vec::<(Position, V)>::deserialize(deserializer)?;
+        Ok(pairs.into_iter().collect())
+    }
+}
+
+/// Serde helpers writing a `Position` as its algebraic notation, e.g. `"e4"`.
+///
+/// Positions serialize as `{file, rank}` numbers by default; use this with
+/// `#[serde(with = "chess_app::types::algebraic")]` where readability matters more, as
+/// `state::Move` does.
+pub mod algebraic {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use super::Position;
+
+    pub fn serialize<S>(pos: &Position, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&pos.to_notation())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Position, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let notation = String::deserialize(deserializer)?;
+        Position::from_notation(&notation)
+            .ok_or_else(|| de::Error::custom(format!("invalid square \"{}\"", notation)))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_color_index_roundtrip() {
+        assert_eq!(Color::White.index(), 0);
+        assert_eq!(Color::Black.index(), 1);
+
+        for color in [Color::White, Color::Black] {
+            assert_eq!(Color::from_index(color.index()), Some(color));
+        }
+        assert_eq!(Color::from_index(2), None);
+    }
+
+    #[test]
+    fn test_sort_captured_by_value() {
+        let mut captured: Vec<ByValue> = [
+            PieceType::Rook,
+            PieceType::Pawn,
+            PieceType::Queen,
+            PieceType::Knight,
+            PieceType::Pawn,
+            PieceType::Bishop,
+        ].iter().map(|&t| ByValue(t)).collect();
+        captured.sort();
+
+        let sorted: Vec<PieceType> = captured.iter().map(|b| b.0).collect();
+        assert_eq!(sorted, vec![
+            PieceType::Pawn,
+            PieceType::Pawn,
+            PieceType::Knight,
+            PieceType::Bishop,
+            PieceType::Rook,
+            PieceType::Queen,
+        ]);
+    }
+
     #[test]
     fn test_position_from_notation() {
         // Test valid notations
@@ -179,6 +484,13 @@ mod tests {
         assert!(Position::from_notation("").is_none());
         assert!(Position::from_notation("a").is_none());
         assert!(Position::from_notation("abc").is_none());
+        
+        // Non-ASCII input is rejected rather than mis-parsed
+        assert!(Position::from_notation("é4").is_none());
+        assert!(Position::from_notation("🙂").is_none());
+        // Two bytes but a single character
+        assert_eq!("é".len(), 2);
+        assert!(Position::from_notation("é").is_none());
     }
     
     #[test]
@@ -198,6 +510,81 @@ mod tests {
         assert!(invalid.to_notation().starts_with("Invalid"));
     }
     
+    #[test]
+    fn test_position_new_checks_bounds() {
+        assert_eq!(Position::new(0, 0), Some(Position { file: 0, rank: 0 }));
+        assert_eq!(Position::new(7, 7), Some(Position { file: 7, rank: 7 }));
+        assert!(Position::new(8, 0).is_none());
+        assert!(Position::new(0, 8).is_none());
+        assert!(Position::new(u8::MAX, u8::MAX).is_none());
+    }
+    
+    #[test]
+    fn test_offset_stays_on_board() {
+        let square = |name: &str| Position::from_notation(name).unwrap();
+        assert_eq!(Position::try_new(7, 7), Some(square("h8")));
+        assert!(Position::try_new(8, 0).is_none());
+        assert!(Position::try_new(0, -1).is_none());
+        
+        // Stepping off an edge never wraps to the far side of the board
+        assert!(square("a1").offset(-1, 0).is_none());
+        assert!(square("a1").offset(0, -1).is_none());
+        assert!(square("h1").offset(1, 0).is_none());
+        assert!(square("h8").offset(0, 1).is_none());
+        assert!(square("e4").offset(i8::MAX, i8::MIN).is_none());
+        assert!(Position { file: 8, rank: 0 }.offset(-1, 0).is_none());
+        
+        assert_eq!(square("a1").offset(0, 0), Some(square("a1")));
+        assert_eq!(square("e4").offset(-4, 4), Some(square("a8")));
+        assert_eq!(square("g1").offset(-1, 2), Some(square("f3")));
+    }
+    
+    #[test]
+    fn test_ray_stops_at_edge() {
+        let square = |name: &str| Position::from_notation(name).unwrap();
+        let ray = |from: &str, file_step, rank_step| square(from).ray(file_step, rank_step).collect::<Vec<_>>();
+        
+        assert_eq!(ray("e4", 0, 1), [square("e5"), square("e6"), square("e7"), square("e8")]);
+        assert_eq!(ray("e4", -1, -1), [square("d3"), square("c2"), square("b1")]);
+        assert!(ray("a1", -1, 0).is_empty());
+        assert!(ray("h8", 1, 1).is_empty());
+        assert!(ray("e4", 0, 0).is_empty());
+        
+        // A rook's four rays cover its rank and file, never its own square
+        let rook_lines: Vec<Position> = [(0, 1), (0, -1), (1, 0), (-1, 0)]
+            .into_iter()
+            .flat_map(|(file_step, rank_step)| square("d4").ray(file_step, rank_step))
+            .collect();
+        assert_eq!(rook_lines.len(), 14);
+        assert!(!rook_lines.contains(&square("d4")));
+        assert!(rook_lines.iter().all(|pos| pos.file == 3 || pos.rank == 3));
+    }
+    
+    #[test]
+    fn test_square_color() {
+        let color = |name: &str| Position::from_notation(name).unwrap().square_color();
+        assert_eq!(color("a1"), Color::Black);
+        assert_eq!(color("h1"), Color::White);
+        assert_eq!(color("a8"), Color::White);
+        assert_eq!(color("h8"), Color::Black);
+        // The queens start on their own color
+        assert_eq!(color("d1"), Color::White);
+        assert_eq!(color("d8"), Color::Black);
+    }
+    
+    #[test]
+    fn test_neighbors() {
+        let a1 = Position::new_unchecked(0, 0);
+        assert_eq!(a1.king_neighbors().count(), 3);
+        assert_eq!(a1.knight_neighbors().count(), 2);
+        assert!(a1.knight_neighbors().any(|pos| pos == Position::new_unchecked(1, 2)));
+        
+        let e4 = Position::new_unchecked(4, 3);
+        assert_eq!(e4.king_neighbors().count(), 8);
+        assert_eq!(e4.knight_neighbors().count(), 8);
+        assert!(e4.king_neighbors().all(|pos| pos != e4 && pos.is_valid()));
+    }
+    
     #[test]
     fn test_notation_roundtrip() {
         // Test conversion in both directions
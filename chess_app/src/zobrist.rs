@@ -0,0 +1,72 @@
+//! Zobrist keys for hashing positions.
+//!
+//! A position's hash is the XOR of one key per piece on its square, plus keys for the
+//! side to move, each castling right still available and the en passant file. Because
+//! XOR is its own inverse, moving a piece only needs that piece's keys XORed out and in.
+
+use crate::types::{Color, Piece, Position};
+
+/// Seed for the generator the keys are drawn from, fixed so hashes are the same in every
+/// build and run
+///
+/// Changing the seed, or the order keys are drawn in, changes every hash and is a breaking
+/// change for anything that stores them, such as saved transposition data.
+const SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
+const PIECE_KINDS: usize = 12;
+const KEY_COUNT: usize = PIECE_KINDS * 64 + 1 + 4 + 8;
+
+const SIDE_TO_MOVE_KEY: usize = PIECE_KINDS * 64;
+const CASTLING_KEYS: usize = SIDE_TO_MOVE_KEY + 1;
+const EN_PASSANT_KEYS: usize = CASTLING_KEYS + 4;
+
+/// All keys, generated at compile time with splitmix64
+const KEYS: [u64; KEY_COUNT] = generate_keys();
+
+const fn generate_keys() -> [u64; KEY_COUNT] {
+    let mut keys = [0; KEY_COUNT];
+    let mut state = SEED;
+    let mut i = 0;
+    while i < KEY_COUNT {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        keys[i] = mix(state);
+        i += 1;
+    }
+    keys
+}
+
+/// Scramble `z` with splitmix64's output function, so that nearby inputs give unrelated outputs
+pub(crate) const fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Key for a piece standing on a square, or 0 for squares off the board
+pub fn piece_key(piece: &Piece, pos: &Position) -> u64 {
+    if !pos.is_valid() {
+        return 0;
+    }
+    let kind = piece.piece_type.value_rank() as usize * 2 + piece.color.index();
+    let square = pos.rank as usize * 8 + pos.file as usize;
+    KEYS[kind * 64 + square]
+}
+
+/// Key XORed in when Black is to move
+pub fn side_to_move_key(side: Color) -> u64 {
+    match side {
+        Color::White => 0,
+        Color::Black => KEYS[SIDE_TO_MOVE_KEY],
+    }
+}
+
+/// Key for one castling right, in FEN order: white kingside, white queenside,
+/// black kingside, black queenside
+pub fn castling_key(index: usize) -> u64 {
+    KEYS[CASTLING_KEYS + index]
+}
+
+/// Key for an en passant capture being possible on the given file
+pub fn en_passant_key(file: u8) -> u64 {
+    KEYS[EN_PASSANT_KEYS + file as usize]
+}
@@ -0,0 +1,35 @@
+use chess_app::{Board, Color, Piece, PieceType, Position};
+
+#[test]
+fn test_new_game_piece_count() {
+    let board = Board::new_game();
+    assert_eq!(board.piece_count(), 32);
+    assert!(!board.is_empty());
+}
+
+#[test]
+fn test_piece_count_tracks_changes() {
+    let mut board = Board::new();
+    assert_eq!(board.piece_count(), 0);
+    assert!(board.is_empty());
+
+    board.set_piece(Position::new_unchecked(4, 0), Piece::new(PieceType::King, Color::White));
+    board.set_piece(Position::new_unchecked(4, 7), Piece::new(PieceType::King, Color::Black));
+    assert_eq!(board.piece_count(), 2);
+
+    board.remove_piece(&Position::new_unchecked(4, 7));
+    assert_eq!(board.piece_count(), 1);
+}
+
+#[test]
+fn test_remove_all_of_for_queen_odds() {
+    let mut board = Board::new_game();
+    assert_eq!(board.remove_all_of(Color::White, PieceType::Queen), 1);
+
+    assert_eq!(board.count_pieces_by_color(Color::White), 15);
+    assert_eq!(board.count_pieces_by_color(Color::Black), 16);
+
+    let white_queen = Piece::new(PieceType::Queen, Color::White);
+    let mut squares = (0..8).flat_map(|rank| (0..8).map(move |file| Position::new_unchecked(file, rank)));
+    assert!(squares.all(|pos| board.get_piece(&pos) != Some(&white_queen)));
+}
@@ -0,0 +1,51 @@
+use chess_app::GameState;
+
+/// Reference positions from the chessprogramming wiki with their perft node counts,
+/// starting at depth 1. Add a row to check another position.
+const POSITIONS: &[(&str, &str, &[u64])] = &[
+    (
+        "start",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        &[20, 400, 8902],
+    ),
+    (
+        "kiwipete",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        &[48, 2039, 97862],
+    ),
+    (
+        "position 3",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        &[14, 191, 2812, 43238],
+    ),
+    (
+        "position 4",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        &[6, 264, 9467],
+    ),
+    (
+        "position 5",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        &[44, 1486, 62379],
+    ),
+];
+
+#[test]
+fn test_perft_reference_positions() {
+    for &(name, fen, counts) in POSITIONS {
+        let mut game = GameState::from_fen(fen).unwrap_or_else(|| panic!("{}: invalid FEN", name));
+        
+        for (depth, &expected) in (1..).zip(counts) {
+            assert_eq!(game.perft(depth), expected, "{} at depth {}", name, depth);
+        }
+    }
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn test_perft_parallel_matches_sequential() {
+    let mut game = GameState::new();
+    
+    assert_eq!(game.perft_parallel(4), 197_281);
+    assert_eq!(game.perft_parallel(4), game.perft(4));
+}